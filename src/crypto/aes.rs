@@ -0,0 +1,274 @@
+#[cfg(feature = "cipher")]
+use cipher::consts::{U1, U16};
+#[cfg(feature = "cipher")]
+use cipher::generic_array::GenericArray;
+#[cfg(feature = "cipher")]
+use cipher::{BlockCipher, BlockDecrypt, BlockEncrypt, NewBlockCipher};
+
+#[cfg(not(test))]
+use crypto::RomCryptoApi;
+use crypto::{zero_volatile, Wipe};
+use crypto::{AES128_KEY_BYTES, AES_BLOCKSIZE_BYTES};
+
+#[cfg(test)]
+use test_crypto::aessafe::{AesSafe128Decryptor, AesSafe128Encryptor};
+#[cfg(test)]
+use test_crypto::symmetriccipher::{BlockDecryptor, BlockEncryptor};
+
+/// AES-128 block operations the cipher modes are built on
+///
+/// Implemented by `Aes128`, which holds a copy of the key, and by
+/// `Aes128Ref`, which borrows one, e.g. from TrustRAM.
+pub trait Aes128Cipher {
+    /// Encrypt a single 128 bit block in place
+    fn encrypt_block(&self, block: &mut [u8; AES_BLOCKSIZE_BYTES]);
+
+    /// Decrypt a single 128 bit block in place
+    fn decrypt_block(&self, block: &mut [u8; AES_BLOCKSIZE_BYTES]);
+
+    /// Clear the key if the cipher holds a copy of it, called when a mode
+    /// using the cipher is wiped
+    fn wipe_key(&mut self) {}
+}
+
+/// AES-128 block cipher using the CRYA `crya_aes_encrypt` / `crya_aes_decrypt`
+/// ROM functions.
+///
+/// The ROM expands the key on every call, so only the raw key is stored.
+#[derive(Clone)]
+pub struct Aes128 {
+    key: [u8; AES128_KEY_BYTES],
+}
+
+impl Aes128 {
+    pub fn new(key: &[u8; AES128_KEY_BYTES]) -> Self {
+        Aes128 { key: *key }
+    }
+
+    /// Encrypt a single 128 bit block in place
+    pub fn encrypt_block(&self, block: &mut [u8; AES_BLOCKSIZE_BYTES]) {
+        let src = *block;
+        self.encrypt(&src, block);
+    }
+
+    /// Decrypt a single 128 bit block in place
+    pub fn decrypt_block(&self, block: &mut [u8; AES_BLOCKSIZE_BYTES]) {
+        let src = *block;
+        self.decrypt(&src, block);
+    }
+
+    fn encrypt(&self, src: &[u8; AES_BLOCKSIZE_BYTES], dst: &mut [u8; AES_BLOCKSIZE_BYTES]) {
+        encrypt(&self.key, src, dst);
+    }
+
+    fn decrypt(&self, src: &[u8; AES_BLOCKSIZE_BYTES], dst: &mut [u8; AES_BLOCKSIZE_BYTES]) {
+        decrypt(&self.key, src, dst);
+    }
+}
+
+/// AES-128 block cipher using a key it borrows instead of holding a copy,
+/// e.g. one kept in TrustRAM by `tram::Tram`
+///
+/// The ROM reads the key from where it is stored on every call. Wiping the
+/// key is left to its owner.
+#[derive(Clone, Copy)]
+pub struct Aes128Ref<'a> {
+    key: &'a [u8; AES128_KEY_BYTES],
+}
+
+impl<'a> Aes128Ref<'a> {
+    pub fn new(key: &'a [u8; AES128_KEY_BYTES]) -> Self {
+        Aes128Ref { key }
+    }
+
+    /// Encrypt a single 128 bit block in place
+    pub fn encrypt_block(&self, block: &mut [u8; AES_BLOCKSIZE_BYTES]) {
+        let src = *block;
+        encrypt(self.key, &src, block);
+    }
+
+    /// Decrypt a single 128 bit block in place
+    pub fn decrypt_block(&self, block: &mut [u8; AES_BLOCKSIZE_BYTES]) {
+        let src = *block;
+        decrypt(self.key, &src, block);
+    }
+}
+
+impl Aes128Cipher for Aes128 {
+    fn encrypt_block(&self, block: &mut [u8; AES_BLOCKSIZE_BYTES]) {
+        Aes128::encrypt_block(self, block);
+    }
+
+    fn decrypt_block(&self, block: &mut [u8; AES_BLOCKSIZE_BYTES]) {
+        Aes128::decrypt_block(self, block);
+    }
+
+    fn wipe_key(&mut self) {
+        self.wipe();
+    }
+}
+
+impl<'a> Aes128Cipher for Aes128Ref<'a> {
+    fn encrypt_block(&self, block: &mut [u8; AES_BLOCKSIZE_BYTES]) {
+        Aes128Ref::encrypt_block(self, block);
+    }
+
+    fn decrypt_block(&self, block: &mut [u8; AES_BLOCKSIZE_BYTES]) {
+        Aes128Ref::decrypt_block(self, block);
+    }
+}
+
+#[cfg(not(test))]
+fn encrypt(
+    key: &[u8; AES128_KEY_BYTES],
+    src: &[u8; AES_BLOCKSIZE_BYTES],
+    dst: &mut [u8; AES_BLOCKSIZE_BYTES],
+) {
+    let rom_api = RomCryptoApi::api_table();
+    rom_api.aes128_encrypt_block(key, src, dst);
+}
+
+#[cfg(test)]
+fn encrypt(
+    key: &[u8; AES128_KEY_BYTES],
+    src: &[u8; AES_BLOCKSIZE_BYTES],
+    dst: &mut [u8; AES_BLOCKSIZE_BYTES],
+) {
+    AesSafe128Encryptor::new(key).encrypt_block(src, dst);
+}
+
+#[cfg(not(test))]
+fn decrypt(
+    key: &[u8; AES128_KEY_BYTES],
+    src: &[u8; AES_BLOCKSIZE_BYTES],
+    dst: &mut [u8; AES_BLOCKSIZE_BYTES],
+) {
+    let rom_api = RomCryptoApi::api_table();
+    rom_api.aes128_decrypt_block(key, src, dst);
+}
+
+#[cfg(test)]
+fn decrypt(
+    key: &[u8; AES128_KEY_BYTES],
+    src: &[u8; AES_BLOCKSIZE_BYTES],
+    dst: &mut [u8; AES_BLOCKSIZE_BYTES],
+) {
+    AesSafe128Decryptor::new(key).decrypt_block(src, dst);
+}
+
+impl Wipe for Aes128 {
+    fn wipe(&mut self) {
+        zero_volatile(&mut self.key);
+    }
+}
+
+#[cfg(feature = "cipher")]
+impl NewBlockCipher for Aes128 {
+    type KeySize = U16;
+
+    fn new(key: &GenericArray<u8, U16>) -> Self {
+        let mut raw_key = [0u8; AES128_KEY_BYTES];
+        raw_key.copy_from_slice(key);
+        Aes128::new(&raw_key)
+    }
+}
+
+#[cfg(feature = "cipher")]
+impl BlockCipher for Aes128 {
+    type BlockSize = U16;
+    type ParBlocks = U1;
+}
+
+#[cfg(feature = "cipher")]
+impl BlockEncrypt for Aes128 {
+    fn encrypt_block(&self, block: &mut GenericArray<u8, U16>) {
+        let mut src = [0u8; AES_BLOCKSIZE_BYTES];
+        src.copy_from_slice(block);
+        let mut dst = [0u8; AES_BLOCKSIZE_BYTES];
+        self.encrypt(&src, &mut dst);
+        block.copy_from_slice(&dst);
+    }
+}
+
+#[cfg(feature = "cipher")]
+impl BlockDecrypt for Aes128 {
+    fn decrypt_block(&self, block: &mut GenericArray<u8, U16>) {
+        let mut src = [0u8; AES_BLOCKSIZE_BYTES];
+        src.copy_from_slice(block);
+        let mut dst = [0u8; AES_BLOCKSIZE_BYTES];
+        self.decrypt(&src, &mut dst);
+        block.copy_from_slice(&dst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // FIPS-197 appendix B and C.1
+    const VECTORS: [([u8; 16], [u8; 16], [u8; 16]); 2] = [
+        (
+            hex!("2b7e151628aed2a6abf7158809cf4f3c"),
+            hex!("3243f6a8885a308d313198a2e0370734"),
+            hex!("3925841d02dc09fbdc118597196a0b32"),
+        ),
+        (
+            hex!("000102030405060708090a0b0c0d0e0f"),
+            hex!("00112233445566778899aabbccddeeff"),
+            hex!("69c4e0d86a7b0430d8cdb78070b4c55a"),
+        ),
+    ];
+
+    #[test]
+    fn test_aes128_fips197() {
+        for &(key, plaintext, ciphertext) in VECTORS.iter() {
+            let aes = Aes128::new(&key);
+
+            let mut block = plaintext;
+            aes.encrypt_block(&mut block);
+            assert_eq!(block, ciphertext);
+
+            aes.decrypt_block(&mut block);
+            assert_eq!(block, plaintext);
+        }
+    }
+
+    #[test]
+    fn test_aes128_ref() {
+        for &(key, plaintext, ciphertext) in VECTORS.iter() {
+            let aes = Aes128Ref::new(&key);
+
+            let mut block = plaintext;
+            aes.encrypt_block(&mut block);
+            assert_eq!(block, ciphertext);
+
+            aes.decrypt_block(&mut block);
+            assert_eq!(block, plaintext);
+        }
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_aes128_zeroize() {
+        use zeroize::Zeroize;
+
+        let mut aes = Aes128::new(&VECTORS[0].0);
+        aes.zeroize();
+        assert_eq!(aes.key, [0u8; 16]);
+    }
+
+    #[cfg(feature = "cipher")]
+    #[test]
+    fn test_aes128_cipher_traits() {
+        for &(key, plaintext, ciphertext) in VECTORS.iter() {
+            let aes = <Aes128 as NewBlockCipher>::new(GenericArray::from_slice(&key));
+
+            let mut block = GenericArray::clone_from_slice(&plaintext);
+            BlockEncrypt::encrypt_block(&aes, &mut block);
+            assert_eq!(block[..], ciphertext[..]);
+
+            BlockDecrypt::decrypt_block(&aes, &mut block);
+            assert_eq!(block[..], plaintext[..]);
+        }
+    }
+}
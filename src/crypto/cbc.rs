@@ -0,0 +1,212 @@
+use crypto::{zero_volatile, Aes128, Aes128Cipher, Error, Wipe};
+use crypto::{AES128_KEY_BYTES, AES_BLOCKSIZE_BYTES};
+
+/// AES-128 in CBC mode (NIST SP 800-38A)
+///
+/// The chaining value is kept between calls, so a message can be processed
+/// in several block aligned pieces.
+#[derive(Clone)]
+pub struct Aes128Cbc<C: Aes128Cipher = Aes128> {
+    cipher: C,
+    iv: [u8; AES_BLOCKSIZE_BYTES],
+}
+
+impl Aes128Cbc {
+    pub fn new(key: &[u8; AES128_KEY_BYTES], iv: &[u8; AES_BLOCKSIZE_BYTES]) -> Self {
+        Aes128Cbc::with_cipher(Aes128::new(key), iv)
+    }
+}
+
+impl<C: Aes128Cipher> Aes128Cbc<C> {
+    /// CBC mode on `cipher`, e.g. an `Aes128Ref` to a key in TrustRAM
+    pub fn with_cipher(cipher: C, iv: &[u8; AES_BLOCKSIZE_BYTES]) -> Self {
+        Aes128Cbc { cipher, iv: *iv }
+    }
+
+    /// Encrypt whole blocks in place, `buffer` must be a multiple of the block size
+    pub fn encrypt_blocks(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        if buffer.len() % AES_BLOCKSIZE_BYTES != 0 {
+            return Err(Error::InvalidLength);
+        }
+
+        for chunk in buffer.chunks_exact_mut(AES_BLOCKSIZE_BYTES) {
+            for (iv, b) in self.iv.iter_mut().zip(chunk.iter()) {
+                *iv ^= b;
+            }
+            self.cipher.encrypt_block(&mut self.iv);
+            chunk.copy_from_slice(&self.iv);
+        }
+
+        Ok(())
+    }
+
+    /// Decrypt whole blocks in place, `buffer` must be a multiple of the block size
+    pub fn decrypt_blocks(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        if buffer.len() % AES_BLOCKSIZE_BYTES != 0 {
+            return Err(Error::InvalidLength);
+        }
+
+        for chunk in buffer.chunks_exact_mut(AES_BLOCKSIZE_BYTES) {
+            let mut block = [0u8; AES_BLOCKSIZE_BYTES];
+            block.copy_from_slice(chunk);
+            let next_iv = block;
+
+            self.cipher.decrypt_block(&mut block);
+            for ((c, b), iv) in chunk.iter_mut().zip(block.iter()).zip(self.iv.iter()) {
+                *c = b ^ iv;
+            }
+            self.iv = next_iv;
+        }
+
+        Ok(())
+    }
+
+    /// PKCS#7 pad the message in `buffer[..msg_len]` and encrypt it in place
+    ///
+    /// `buffer` needs room for up to one extra block of padding, the
+    /// ciphertext is returned as a subslice of `buffer`.
+    pub fn encrypt_padded(mut self, buffer: &mut [u8], msg_len: usize) -> Result<&[u8], Error> {
+        let pad_len = AES_BLOCKSIZE_BYTES - msg_len % AES_BLOCKSIZE_BYTES;
+        let padded_len = msg_len + pad_len;
+        if padded_len > buffer.len() {
+            return Err(Error::InvalidLength);
+        }
+
+        for b in buffer[msg_len..padded_len].iter_mut() {
+            *b = pad_len as u8;
+        }
+
+        self.encrypt_blocks(&mut buffer[..padded_len])?;
+        Ok(&buffer[..padded_len])
+    }
+
+    /// Decrypt `buffer` in place and strip the PKCS#7 padding
+    ///
+    /// The plaintext is returned as a subslice of `buffer`.
+    pub fn decrypt_padded(mut self, buffer: &mut [u8]) -> Result<&[u8], Error> {
+        if buffer.is_empty() {
+            return Err(Error::InvalidLength);
+        }
+        self.decrypt_blocks(buffer)?;
+
+        let pad_len = buffer[buffer.len() - 1];
+        // Check every byte of the last block so the time taken does not depend on
+        // where the padding is broken
+        let last_block = &buffer[buffer.len() - AES_BLOCKSIZE_BYTES..];
+        let mut bad = (pad_len == 0) as u8 | (pad_len as usize > AES_BLOCKSIZE_BYTES) as u8;
+        for (i, b) in last_block.iter().rev().enumerate() {
+            let in_pad = ((i as u8) < pad_len) as u8;
+            bad |= in_pad & ((*b != pad_len) as u8);
+        }
+
+        if bad != 0 {
+            return Err(Error::InvalidPadding);
+        }
+
+        Ok(&buffer[..buffer.len() - pad_len as usize])
+    }
+}
+
+impl<C: Aes128Cipher> Wipe for Aes128Cbc<C> {
+    fn wipe(&mut self) {
+        self.cipher.wipe_key();
+        zero_volatile(&mut self.iv);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::Aes128Ref;
+
+    const KEY: [u8; 16] = hex!("2b7e151628aed2a6abf7158809cf4f3c");
+    const IV: [u8; 16] = hex!("000102030405060708090a0b0c0d0e0f");
+    const PLAINTEXT: [u8; 64] = hex!(
+        "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51"
+        "30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710"
+    );
+    const CIPHERTEXT: [u8; 64] = hex!(
+        "7649abac8119b246cee98e9b12e9197d5086cb9b507219ee95db113a917678b2"
+        "73bed6b8e3c1743b7116e69e222295163ff1caa1681fac09120eca307586e1a7"
+    );
+
+    // NIST SP 800-38A F.2.1 and F.2.2
+    #[test]
+    fn test_aes128_cbc_sp800_38a() {
+        let mut buffer = PLAINTEXT;
+        Aes128Cbc::new(&KEY, &IV).encrypt_blocks(&mut buffer).unwrap();
+        assert_eq!(buffer[..], CIPHERTEXT[..]);
+
+        Aes128Cbc::new(&KEY, &IV).decrypt_blocks(&mut buffer).unwrap();
+        assert_eq!(buffer[..], PLAINTEXT[..]);
+
+        // Chaining carries over between calls
+        let mut buffer = PLAINTEXT;
+        let mut cbc = Aes128Cbc::new(&KEY, &IV);
+        let (first, second) = buffer.split_at_mut(16);
+        cbc.encrypt_blocks(first).unwrap();
+        cbc.encrypt_blocks(second).unwrap();
+        assert_eq!(buffer[..], CIPHERTEXT[..]);
+
+        assert_eq!(
+            Aes128Cbc::new(&KEY, &IV).encrypt_blocks(&mut [0u8; 15]),
+            Err(Error::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn test_aes128_cbc_pkcs7() {
+        let mut buffer = [0u8; 64];
+        buffer[..20].copy_from_slice(&PLAINTEXT[..20]);
+        let len = Aes128Cbc::new(&KEY, &IV)
+            .encrypt_padded(&mut buffer, 20)
+            .unwrap()
+            .len();
+        assert_eq!(len, 32);
+        assert_eq!(buffer[..16], CIPHERTEXT[..16]);
+
+        let plaintext = Aes128Cbc::new(&KEY, &IV)
+            .decrypt_padded(&mut buffer[..len])
+            .unwrap();
+        assert_eq!(plaintext[..], PLAINTEXT[..20]);
+
+        // Block aligned messages get a full block of padding
+        let mut buffer = [0u8; 48];
+        buffer[..32].copy_from_slice(&PLAINTEXT[..32]);
+        let ciphertext = Aes128Cbc::new(&KEY, &IV)
+            .encrypt_padded(&mut buffer, 32)
+            .unwrap();
+        assert_eq!(ciphertext[..32], CIPHERTEXT[..32]);
+        assert_eq!(
+            Aes128Cbc::new(&KEY, &IV).decrypt_padded(&mut buffer),
+            Ok(&PLAINTEXT[..32])
+        );
+
+        let mut buffer = [0u8; 32];
+        assert_eq!(
+            Aes128Cbc::new(&KEY, &IV).encrypt_padded(&mut buffer, 32),
+            Err(Error::InvalidLength)
+        );
+
+        // Unpadded ciphertext is rejected
+        let mut buffer = CIPHERTEXT;
+        assert_eq!(
+            Aes128Cbc::new(&KEY, &IV).decrypt_padded(&mut buffer),
+            Err(Error::InvalidPadding)
+        );
+    }
+
+    #[test]
+    fn test_aes128_cbc_ref() {
+        let mut buffer = PLAINTEXT;
+        Aes128Cbc::with_cipher(Aes128Ref::new(&KEY), &IV)
+            .encrypt_blocks(&mut buffer)
+            .unwrap();
+        assert_eq!(buffer[..], CIPHERTEXT[..]);
+
+        Aes128Cbc::with_cipher(Aes128Ref::new(&KEY), &IV)
+            .decrypt_blocks(&mut buffer)
+            .unwrap();
+        assert_eq!(buffer[..], PLAINTEXT[..]);
+    }
+}
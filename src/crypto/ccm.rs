@@ -0,0 +1,399 @@
+use crypto::{constant_time_eq, zero_volatile, Aes128, Aes128Cipher, Error, Wipe};
+use crypto::{AES128_KEY_BYTES, AES_BLOCKSIZE_BYTES};
+
+type Block = [u8; AES_BLOCKSIZE_BYTES];
+
+/// AES-128-CCM authenticated encryption (NIST SP 800-38C, RFC 3610)
+///
+/// The tag length can be 4, 6, 8, 10, 12, 14 or 16 bytes and the nonce length
+/// 7 to 13 bytes. The nonce length fixes the maximum message length, a 13 byte
+/// nonce as used by 802.15.4 and BLE allows messages up to 64KiB.
+///
+/// A tag length of 0 is the encryption only mode of CCM* used by the
+/// 802.15.4 ENC security levels. It takes an empty tag, ignores the
+/// associated data and provides no authentication at all.
+#[derive(Clone)]
+pub struct Aes128Ccm<C: Aes128Cipher = Aes128> {
+    cipher: C,
+    tag_len: usize,
+    nonce_len: usize,
+}
+
+impl Aes128Ccm {
+    pub fn new(key: &[u8; AES128_KEY_BYTES], tag_len: usize, nonce_len: usize) -> Result<Self, Error> {
+        Aes128Ccm::with_cipher(Aes128::new(key), tag_len, nonce_len)
+    }
+}
+
+impl<C: Aes128Cipher> Aes128Ccm<C> {
+    /// CCM on `cipher`, e.g. an `Aes128Ref` to a key in TrustRAM
+    pub fn with_cipher(cipher: C, tag_len: usize, nonce_len: usize) -> Result<Self, Error> {
+        let tag_valid = tag_len == 0 || ((4..=16).contains(&tag_len) && tag_len % 2 == 0);
+        if !tag_valid || !(7..=13).contains(&nonce_len) {
+            return Err(Error::InvalidLength);
+        }
+
+        Ok(Aes128Ccm {
+            cipher,
+            tag_len,
+            nonce_len,
+        })
+    }
+
+    /// Encrypt `buffer` in place and write the authentication tag over
+    /// `associated_data` and the message into `tag`
+    pub fn encrypt_in_place_detached(
+        &self,
+        nonce: &[u8],
+        associated_data: &[u8],
+        buffer: &mut [u8],
+        tag: &mut [u8],
+    ) -> Result<(), Error> {
+        self.check_lengths(nonce, buffer, tag)?;
+
+        if self.tag_len == 0 {
+            self.apply_keystream(nonce, buffer);
+            return Ok(());
+        }
+
+        let mut mac = self.cbc_mac(nonce, associated_data, buffer);
+        self.apply_keystream(nonce, buffer);
+        self.encrypt_tag(nonce, &mac, tag);
+        zero_volatile(&mut mac);
+
+        Ok(())
+    }
+
+    /// Decrypt `buffer` in place and check `tag` over `associated_data` and the
+    /// decrypted message
+    ///
+    /// The buffer is restored to the ciphertext when verification fails.
+    pub fn decrypt_in_place_detached(
+        &self,
+        nonce: &[u8],
+        associated_data: &[u8],
+        buffer: &mut [u8],
+        tag: &[u8],
+    ) -> Result<(), Error> {
+        self.check_lengths(nonce, buffer, tag)?;
+
+        self.apply_keystream(nonce, buffer);
+        if self.tag_len == 0 {
+            return Ok(());
+        }
+
+        let mut mac = self.cbc_mac(nonce, associated_data, buffer);
+        let mut expected = [0u8; AES_BLOCKSIZE_BYTES];
+        self.encrypt_tag(nonce, &mac, &mut expected[..self.tag_len]);
+        let valid = constant_time_eq(&expected[..self.tag_len], tag);
+        zero_volatile(&mut mac);
+        zero_volatile(&mut expected);
+
+        if valid {
+            Ok(())
+        } else {
+            self.apply_keystream(nonce, buffer);
+            Err(Error::VerificationFailed)
+        }
+    }
+
+    /// Size of the message length field in the first block
+    fn length_field_len(&self) -> usize {
+        AES_BLOCKSIZE_BYTES - 1 - self.nonce_len
+    }
+
+    fn check_lengths(&self, nonce: &[u8], buffer: &[u8], tag: &[u8]) -> Result<(), Error> {
+        let length_field_bits = self.length_field_len() * 8;
+        let too_long = length_field_bits < 64 && (buffer.len() as u64) >> length_field_bits != 0;
+
+        if nonce.len() != self.nonce_len || tag.len() != self.tag_len || too_long {
+            Err(Error::InvalidLength)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Counter block `index` with the nonce and flags filled in
+    fn counter_block(&self, nonce: &[u8], index: u64) -> Block {
+        let mut block = [0u8; AES_BLOCKSIZE_BYTES];
+        block[0] = (self.length_field_len() - 1) as u8;
+        block[1..1 + self.nonce_len].copy_from_slice(nonce);
+        write_be(&mut block[1 + self.nonce_len..], index);
+        block
+    }
+
+    fn apply_keystream(&self, nonce: &[u8], buffer: &mut [u8]) {
+        for (i, chunk) in buffer.chunks_mut(AES_BLOCKSIZE_BYTES).enumerate() {
+            let mut keystream = self.counter_block(nonce, i as u64 + 1);
+            self.cipher.encrypt_block(&mut keystream);
+            for (b, k) in chunk.iter_mut().zip(keystream.iter()) {
+                *b ^= k;
+            }
+            zero_volatile(&mut keystream);
+        }
+    }
+
+    fn encrypt_tag(&self, nonce: &[u8], mac: &Block, tag: &mut [u8]) {
+        let mut s0 = self.counter_block(nonce, 0);
+        self.cipher.encrypt_block(&mut s0);
+        for ((t, m), s) in tag.iter_mut().zip(mac.iter()).zip(s0.iter()) {
+            *t = m ^ s;
+        }
+        zero_volatile(&mut s0);
+    }
+
+    fn cbc_mac(&self, nonce: &[u8], associated_data: &[u8], message: &[u8]) -> Block {
+        let mut b0 = [0u8; AES_BLOCKSIZE_BYTES];
+        let adata = if associated_data.is_empty() { 0 } else { 1 << 6 };
+        b0[0] = adata | (((self.tag_len - 2) / 2) << 3) as u8 | (self.length_field_len() - 1) as u8;
+        b0[1..1 + self.nonce_len].copy_from_slice(nonce);
+        write_be(&mut b0[1 + self.nonce_len..], message.len() as u64);
+
+        let mut mac = CbcMac::new(&self.cipher);
+        mac.update(&b0);
+
+        if !associated_data.is_empty() {
+            let len = associated_data.len() as u64;
+            let mut encoded_len = [0u8; 10];
+            let encoded_len = if len < 0xff00 {
+                write_be(&mut encoded_len[..2], len);
+                &encoded_len[..2]
+            } else if len <= 0xffff_ffff {
+                encoded_len[..2].copy_from_slice(&[0xff, 0xfe]);
+                write_be(&mut encoded_len[2..6], len);
+                &encoded_len[..6]
+            } else {
+                encoded_len[..2].copy_from_slice(&[0xff, 0xff]);
+                write_be(&mut encoded_len[2..], len);
+                &encoded_len[..]
+            };
+            mac.update(encoded_len);
+            mac.update(associated_data);
+            mac.pad();
+        }
+
+        mac.update(message);
+        mac.pad();
+        mac.state
+    }
+}
+
+impl<C: Aes128Cipher> Wipe for Aes128Ccm<C> {
+    fn wipe(&mut self) {
+        self.cipher.wipe_key();
+    }
+}
+
+/// Write the low bytes of `value` big endian into `out`
+fn write_be(out: &mut [u8], value: u64) {
+    let bytes = value.to_be_bytes();
+    let len = out.len();
+    out.copy_from_slice(&bytes[bytes.len() - len..]);
+}
+
+/// CBC-MAC with a zero IV over data that is fed in pieces
+struct CbcMac<'a, C: 'a> {
+    cipher: &'a C,
+    state: Block,
+    pos: usize,
+}
+
+impl<'a, C: Aes128Cipher> CbcMac<'a, C> {
+    fn new(cipher: &'a C) -> Self {
+        CbcMac {
+            cipher,
+            state: [0u8; AES_BLOCKSIZE_BYTES],
+            pos: 0,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for b in data {
+            self.state[self.pos] ^= b;
+            self.pos += 1;
+            if self.pos == AES_BLOCKSIZE_BYTES {
+                self.cipher.encrypt_block(&mut self.state);
+                self.pos = 0;
+            }
+        }
+    }
+
+    /// Zero pad up to the next block boundary
+    fn pad(&mut self) {
+        if self.pos != 0 {
+            self.cipher.encrypt_block(&mut self.state);
+            self.pos = 0;
+        }
+    }
+}
+
+impl<'a, C: 'a> Drop for CbcMac<'a, C> {
+    fn drop(&mut self) {
+        zero_volatile(&mut self.state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::Aes128Ref;
+
+    const KEY: [u8; 16] = hex!("404142434445464748494a4b4c4d4e4f");
+
+    fn check_vector(
+        key: &[u8; 16],
+        nonce: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+        ciphertext: &[u8],
+        tag: &[u8],
+    ) {
+        let ccm = Aes128Ccm::new(key, tag.len(), nonce.len()).unwrap();
+
+        let mut buffer = [0u8; 64];
+        let buffer = &mut buffer[..plaintext.len()];
+        buffer.copy_from_slice(plaintext);
+        let mut computed_tag = [0u8; 16];
+        let computed_tag = &mut computed_tag[..tag.len()];
+        ccm.encrypt_in_place_detached(nonce, aad, buffer, computed_tag)
+            .unwrap();
+        assert_eq!(buffer[..], ciphertext[..]);
+        assert_eq!(computed_tag[..], tag[..]);
+
+        ccm.decrypt_in_place_detached(nonce, aad, buffer, tag).unwrap();
+        assert_eq!(buffer[..], plaintext[..]);
+    }
+
+    // NIST SP 800-38C appendix C
+    #[test]
+    fn test_aes128_ccm_sp800_38c() {
+        check_vector(
+            &KEY,
+            &hex!("10111213141516"),
+            &hex!("0001020304050607"),
+            &hex!("20212223"),
+            &hex!("7162015b"),
+            &hex!("4dac255d"),
+        );
+
+        check_vector(
+            &KEY,
+            &hex!("1011121314151617"),
+            &hex!("000102030405060708090a0b0c0d0e0f"),
+            &hex!("202122232425262728292a2b2c2d2e2f"),
+            &hex!("d2a1f0e051ea5f62081a7792073d593d"),
+            &hex!("1fc64fbfaccd"),
+        );
+
+        check_vector(
+            &KEY,
+            &hex!("101112131415161718191a1b"),
+            &hex!("000102030405060708090a0b0c0d0e0f10111213"),
+            &hex!("202122232425262728292a2b2c2d2e2f3031323334353637"),
+            &hex!("e3b201a9f5b71a7a9b1ceaeccd97e70b6176aad9a4428aa5"),
+            &hex!("484392fbc1b09951"),
+        );
+    }
+
+    // RFC 3610 packet vector #1, 13 byte nonce as used by 802.15.4
+    #[test]
+    fn test_aes128_ccm_rfc3610() {
+        check_vector(
+            &hex!("c0c1c2c3c4c5c6c7c8c9cacbcccdcecf"),
+            &hex!("00000003020100a0a1a2a3a4a5"),
+            &hex!("0001020304050607"),
+            &hex!("08090a0b0c0d0e0f101112131415161718191a1b1c1d1e"),
+            &hex!("588c979a61c663d2f066d0c2c0f989806d5f6b61dac384"),
+            &hex!("17e8d12cfdf926e0"),
+        );
+    }
+
+    #[test]
+    fn test_aes128_ccm_verify() {
+        let ccm = Aes128Ccm::new(&KEY, 16, 13).unwrap();
+        let nonce = hex!("101112131415161718191a1b1c");
+        let plaintext = hex!("202122232425262728292a2b2c2d2e2f30");
+
+        let mut buffer = plaintext;
+        let mut tag = [0u8; 16];
+        ccm.encrypt_in_place_detached(&nonce, b"header", &mut buffer, &mut tag)
+            .unwrap();
+        let ciphertext = buffer;
+
+        tag[15] ^= 1;
+        assert_eq!(
+            ccm.decrypt_in_place_detached(&nonce, b"header", &mut buffer, &tag),
+            Err(Error::VerificationFailed)
+        );
+        assert_eq!(buffer, ciphertext);
+
+        tag[15] ^= 1;
+        assert_eq!(
+            ccm.decrypt_in_place_detached(&nonce, b"header", &mut buffer, &tag),
+            Ok(())
+        );
+        assert_eq!(buffer, plaintext);
+    }
+
+    #[test]
+    fn test_aes128_ccm_parameters() {
+        assert!(Aes128Ccm::new(&KEY, 0, 13).is_ok());
+        assert!(Aes128Ccm::new(&KEY, 4, 13).is_ok());
+        assert!(Aes128Ccm::new(&KEY, 8, 7).is_ok());
+        assert!(Aes128Ccm::new(&KEY, 2, 13).is_err());
+        assert!(Aes128Ccm::new(&KEY, 5, 13).is_err());
+        assert!(Aes128Ccm::new(&KEY, 18, 13).is_err());
+        assert!(Aes128Ccm::new(&KEY, 16, 6).is_err());
+        assert!(Aes128Ccm::new(&KEY, 16, 14).is_err());
+
+        let ccm = Aes128Ccm::new(&KEY, 8, 13).unwrap();
+        let mut tag = [0u8; 8];
+        assert_eq!(
+            ccm.encrypt_in_place_detached(&[0u8; 12], &[], &mut [0u8; 4], &mut tag),
+            Err(Error::InvalidLength)
+        );
+        assert_eq!(
+            ccm.encrypt_in_place_detached(&[0u8; 13], &[], &mut [0u8; 4], &mut [0u8; 4]),
+            Err(Error::InvalidLength)
+        );
+    }
+
+    // The CCM* encryption only mode produces the CCM ciphertext without a tag,
+    // here that of NIST SP 800-38C example 2
+    #[test]
+    fn test_aes128_ccm_star_enc_only() {
+        let ccm = Aes128Ccm::new(&KEY, 0, 8).unwrap();
+        let nonce = hex!("1011121314151617");
+        let mut buffer = hex!("202122232425262728292a2b2c2d2e2f");
+        ccm.encrypt_in_place_detached(&nonce, b"header", &mut buffer, &mut [])
+            .unwrap();
+        assert_eq!(buffer, hex!("d2a1f0e051ea5f62081a7792073d593d"));
+
+        ccm.decrypt_in_place_detached(&nonce, &[], &mut buffer, &[])
+            .unwrap();
+        assert_eq!(buffer, hex!("202122232425262728292a2b2c2d2e2f"));
+
+        assert_eq!(
+            ccm.encrypt_in_place_detached(&nonce, &[], &mut buffer, &mut [0u8; 4]),
+            Err(Error::InvalidLength)
+        );
+    }
+
+    // NIST SP 800-38C example 2
+    #[test]
+    fn test_aes128_ccm_ref() {
+        let ccm = Aes128Ccm::with_cipher(Aes128Ref::new(&KEY), 6, 8).unwrap();
+        let nonce = hex!("1011121314151617");
+        let aad = hex!("000102030405060708090a0b0c0d0e0f");
+        let mut buffer = hex!("202122232425262728292a2b2c2d2e2f");
+        let mut tag = [0u8; 6];
+        ccm.encrypt_in_place_detached(&nonce, &aad, &mut buffer, &mut tag)
+            .unwrap();
+        assert_eq!(buffer, hex!("d2a1f0e051ea5f62081a7792073d593d"));
+        assert_eq!(tag, hex!("1fc64fbfaccd"));
+
+        ccm.decrypt_in_place_detached(&nonce, &aad, &mut buffer, &tag)
+            .unwrap();
+        assert_eq!(buffer, hex!("202122232425262728292a2b2c2d2e2f"));
+    }
+}
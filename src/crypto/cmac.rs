@@ -0,0 +1,175 @@
+use crypto::{constant_time_eq, zero_volatile, Aes128, Aes128Cipher, Error, Wipe};
+use crypto::{AES128_KEY_BYTES, AES_BLOCKSIZE_BYTES};
+
+type Block = [u8; AES_BLOCKSIZE_BYTES];
+
+/// AES-CMAC (RFC 4493, NIST SP 800-38B)
+#[derive(Clone)]
+pub struct Aes128Cmac<C: Aes128Cipher = Aes128> {
+    cipher: C,
+    k1: Block,
+    k2: Block,
+    state: Block,
+    buffer: Block,
+    buffer_len: usize,
+}
+
+impl Aes128Cmac {
+    pub fn new(key: &[u8; AES128_KEY_BYTES]) -> Self {
+        Aes128Cmac::with_cipher(Aes128::new(key))
+    }
+}
+
+impl<C: Aes128Cipher> Aes128Cmac<C> {
+    /// CMAC on `cipher`, e.g. an `Aes128Ref` to a key in TrustRAM
+    pub fn with_cipher(cipher: C) -> Self {
+        let mut l = [0u8; AES_BLOCKSIZE_BYTES];
+        cipher.encrypt_block(&mut l);
+        let k1 = dbl(&l);
+        let k2 = dbl(&k1);
+
+        Aes128Cmac {
+            cipher,
+            k1,
+            k2,
+            state: [0u8; AES_BLOCKSIZE_BYTES],
+            buffer: [0u8; AES_BLOCKSIZE_BYTES],
+            buffer_len: 0,
+        }
+    }
+
+    /// Feed `data` into the MAC, may be called any number of times
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            // The last block is special cased in finalize, so a full buffer is
+            // only processed once more data shows up
+            if self.buffer_len == AES_BLOCKSIZE_BYTES {
+                for (s, b) in self.state.iter_mut().zip(self.buffer.iter()) {
+                    *s ^= b;
+                }
+                self.cipher.encrypt_block(&mut self.state);
+                self.buffer_len = 0;
+            }
+
+            let n = core::cmp::min(AES_BLOCKSIZE_BYTES - self.buffer_len, data.len());
+            self.buffer[self.buffer_len..self.buffer_len + n].copy_from_slice(&data[..n]);
+            self.buffer_len += n;
+            data = &data[n..];
+        }
+    }
+
+    /// Return the 128 bit authentication tag
+    pub fn finalize(mut self) -> [u8; AES_BLOCKSIZE_BYTES] {
+        let subkey = if self.buffer_len == AES_BLOCKSIZE_BYTES {
+            self.k1
+        } else {
+            self.buffer[self.buffer_len] = 0x80;
+            for b in self.buffer[self.buffer_len + 1..].iter_mut() {
+                *b = 0;
+            }
+            self.k2
+        };
+
+        for ((s, b), k) in self.state.iter_mut().zip(self.buffer.iter()).zip(subkey.iter()) {
+            *s ^= b ^ k;
+        }
+        self.cipher.encrypt_block(&mut self.state);
+        self.state
+    }
+
+    /// Check `tag` against the computed tag in constant time
+    pub fn verify(self, tag: &[u8]) -> Result<(), Error> {
+        if constant_time_eq(&self.finalize(), tag) {
+            Ok(())
+        } else {
+            Err(Error::VerificationFailed)
+        }
+    }
+}
+
+impl<C: Aes128Cipher> Wipe for Aes128Cmac<C> {
+    fn wipe(&mut self) {
+        self.cipher.wipe_key();
+        zero_volatile(&mut self.k1);
+        zero_volatile(&mut self.k2);
+        zero_volatile(&mut self.state);
+        zero_volatile(&mut self.buffer);
+        self.buffer_len = 0;
+    }
+}
+
+/// Multiply by x in GF(2^128) with the CMAC polynomial
+fn dbl(block: &Block) -> Block {
+    let value = u128::from_be_bytes(*block);
+    let carry = (value >> 127) as u8;
+    let mut ret = (value << 1).to_be_bytes();
+    ret[AES_BLOCKSIZE_BYTES - 1] ^= 0x87 & carry.wrapping_neg();
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::Aes128Ref;
+
+    const KEY: [u8; 16] = hex!("2b7e151628aed2a6abf7158809cf4f3c");
+    const MESSAGE: [u8; 64] = hex!(
+        "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51"
+        "30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710"
+    );
+
+    fn cmac(data: &[u8]) -> [u8; 16] {
+        let mut mac = Aes128Cmac::new(&KEY);
+        mac.update(data);
+        mac.finalize()
+    }
+
+    // RFC 4493 section 4, NIST SP 800-38B D.1
+    #[test]
+    fn test_aes128_cmac_rfc4493() {
+        let mac = Aes128Cmac::new(&KEY);
+        assert_eq!(mac.k1, hex!("fbeed618357133667c85e08f7236a8de"));
+        assert_eq!(mac.k2, hex!("f7ddac306ae266ccf90bc11ee46d513b"));
+
+        assert_eq!(cmac(&[]), hex!("bb1d6929e95937287fa37d129b756746"));
+        assert_eq!(cmac(&MESSAGE[..16]), hex!("070a16b46b4d4144f79bdd9dd04a287c"));
+        assert_eq!(cmac(&MESSAGE[..40]), hex!("dfa66747de9ae63030ca32611497c827"));
+        assert_eq!(cmac(&MESSAGE), hex!("51f0bebf7e3b9d92fc49741779363cfe"));
+    }
+
+    #[test]
+    fn test_aes128_cmac_streaming() {
+        let mut mac = Aes128Cmac::new(&KEY);
+        for chunk in MESSAGE.chunks(16) {
+            mac.update(chunk);
+        }
+        assert_eq!(mac.finalize(), hex!("51f0bebf7e3b9d92fc49741779363cfe"));
+
+        let mut mac = Aes128Cmac::new(&KEY);
+        for chunk in MESSAGE[..40].chunks(3) {
+            mac.update(chunk);
+        }
+        assert_eq!(mac.finalize(), hex!("dfa66747de9ae63030ca32611497c827"));
+    }
+
+    #[test]
+    fn test_aes128_cmac_verify() {
+        let mut mac = Aes128Cmac::new(&KEY);
+        mac.update(&MESSAGE);
+        assert_eq!(mac.verify(&hex!("51f0bebf7e3b9d92fc49741779363cfe")), Ok(()));
+
+        let mut mac = Aes128Cmac::new(&KEY);
+        mac.update(&MESSAGE[..63]);
+        assert_eq!(
+            mac.verify(&hex!("51f0bebf7e3b9d92fc49741779363cfe")),
+            Err(Error::VerificationFailed)
+        );
+    }
+
+    #[test]
+    fn test_aes128_cmac_ref() {
+        let mut mac = Aes128Cmac::with_cipher(Aes128Ref::new(&KEY));
+        mac.update(&MESSAGE);
+        assert_eq!(mac.finalize(), hex!("51f0bebf7e3b9d92fc49741779363cfe"));
+    }
+}
@@ -0,0 +1,106 @@
+use crypto::{zero_volatile, Aes128, Aes128Cipher, Wipe};
+use crypto::{AES128_KEY_BYTES, AES_BLOCKSIZE_BYTES};
+
+/// AES-128 in CTR mode (NIST SP 800-38A)
+///
+/// The whole 128 bit counter block is incremented as a big endian integer.
+/// Unused keystream is kept, so data can be processed in pieces of any length.
+#[derive(Clone)]
+pub struct Aes128Ctr<C: Aes128Cipher = Aes128> {
+    cipher: C,
+    counter: [u8; AES_BLOCKSIZE_BYTES],
+    keystream: [u8; AES_BLOCKSIZE_BYTES],
+    keystream_pos: usize,
+}
+
+impl Aes128Ctr {
+    pub fn new(key: &[u8; AES128_KEY_BYTES], initial_counter: &[u8; AES_BLOCKSIZE_BYTES]) -> Self {
+        Aes128Ctr::with_cipher(Aes128::new(key), initial_counter)
+    }
+}
+
+impl<C: Aes128Cipher> Aes128Ctr<C> {
+    /// CTR mode on `cipher`, e.g. an `Aes128Ref` to a key in TrustRAM
+    pub fn with_cipher(cipher: C, initial_counter: &[u8; AES_BLOCKSIZE_BYTES]) -> Self {
+        Aes128Ctr {
+            cipher,
+            counter: *initial_counter,
+            keystream: [0u8; AES_BLOCKSIZE_BYTES],
+            keystream_pos: AES_BLOCKSIZE_BYTES,
+        }
+    }
+
+    /// XOR the keystream into `data`, encryption and decryption are the same operation
+    pub fn apply_keystream(&mut self, data: &mut [u8]) {
+        for b in data.iter_mut() {
+            if self.keystream_pos == AES_BLOCKSIZE_BYTES {
+                self.refill_keystream();
+            }
+            *b ^= self.keystream[self.keystream_pos];
+            self.keystream_pos += 1;
+        }
+    }
+
+    fn refill_keystream(&mut self) {
+        self.keystream = self.counter;
+        self.cipher.encrypt_block(&mut self.keystream);
+        self.keystream_pos = 0;
+
+        let counter = u128::from_be_bytes(self.counter).wrapping_add(1);
+        self.counter = counter.to_be_bytes();
+    }
+}
+
+impl<C: Aes128Cipher> Wipe for Aes128Ctr<C> {
+    fn wipe(&mut self) {
+        self.cipher.wipe_key();
+        zero_volatile(&mut self.counter);
+        zero_volatile(&mut self.keystream);
+        self.keystream_pos = AES_BLOCKSIZE_BYTES;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::Aes128Ref;
+
+    const KEY: [u8; 16] = hex!("2b7e151628aed2a6abf7158809cf4f3c");
+    const COUNTER: [u8; 16] = hex!("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+    const PLAINTEXT: [u8; 64] = hex!(
+        "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51"
+        "30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710"
+    );
+    const CIPHERTEXT: [u8; 64] = hex!(
+        "874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff"
+        "5ae4df3edbd5d35e5b4f09020db03eab1e031dda2fbe03d1792170a0f3009cee"
+    );
+
+    // NIST SP 800-38A F.5.1 and F.5.2
+    #[test]
+    fn test_aes128_ctr_sp800_38a() {
+        let mut buffer = PLAINTEXT;
+        Aes128Ctr::new(&KEY, &COUNTER).apply_keystream(&mut buffer);
+        assert_eq!(buffer[..], CIPHERTEXT[..]);
+
+        Aes128Ctr::new(&KEY, &COUNTER).apply_keystream(&mut buffer);
+        assert_eq!(buffer[..], PLAINTEXT[..]);
+    }
+
+    #[test]
+    fn test_aes128_ctr_streaming() {
+        let mut buffer = PLAINTEXT;
+        let mut ctr = Aes128Ctr::new(&KEY, &COUNTER);
+        for chunk in buffer.chunks_mut(7) {
+            ctr.apply_keystream(chunk);
+        }
+        assert_eq!(buffer[..], CIPHERTEXT[..]);
+    }
+
+    #[test]
+    fn test_aes128_ctr_ref() {
+        let mut buffer = PLAINTEXT;
+        Aes128Ctr::with_cipher(Aes128Ref::new(&KEY), &COUNTER).apply_keystream(&mut buffer);
+        assert_eq!(buffer[..], CIPHERTEXT[..]);
+    }
+}
@@ -0,0 +1,341 @@
+//! HMAC-DRBG (NIST SP 800-90A) on top of the ROM backed `HmacSha256`
+//!
+//! Reading the TRNG directly stalls for every 32 bit word, the DRBG only
+//! pulls fresh entropy when it is instantiated and when it reseeds.
+
+use core::{mem, ptr};
+
+use hal::blocking::rng::Read;
+#[cfg(feature = "rand_core")]
+use rand_core::{CryptoRng, Error as RandError, ErrorKind as RandErrorKind, RngCore};
+#[cfg(feature = "zeroize")]
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crypto::{zero_volatile, HmacSha256, Wipe, SHA256_STATE_LEN};
+
+const OUTLEN: usize = SHA256_STATE_LEN * 4;
+
+/// Bytes of entropy drawn from the source on instantiation and every reseed,
+/// for a security strength of 256 bits
+pub const ENTROPY_LEN: usize = 32;
+/// Bytes of nonce drawn from the source on instantiation
+pub const NONCE_LEN: usize = 16;
+/// Maximum number of bytes a single `generate` call may request (2^19 bits)
+pub const MAX_REQUEST_BYTES: usize = 1 << 16;
+/// A reasonable number of `generate` calls between reseeds
+pub const DEFAULT_RESEED_INTERVAL: u32 = 1024;
+
+/// DRBG failures
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error<E> {
+    /// The entropy source returned an error
+    Entropy(E),
+    /// More than `MAX_REQUEST_BYTES` were requested at once
+    RequestTooLarge,
+}
+
+/// HMAC-DRBG with SHA-256, seeded from an entropy source such as `rng::Rng`
+///
+/// After `reseed_interval` calls to `generate` the next call reseeds from the
+/// entropy source first. The internal state is wiped when dropped.
+pub struct HmacDrbg<R> {
+    entropy: R,
+    key: [u8; OUTLEN],
+    v: [u8; OUTLEN],
+    reseed_counter: u32,
+    reseed_interval: u32,
+}
+
+impl<R: Read> HmacDrbg<R> {
+    /// Instantiate the DRBG, drawing entropy and a nonce from `entropy`
+    ///
+    /// `personalization` is an optional, not necessarily secret, string that
+    /// makes this instance unique, e.g. a device serial number. A
+    /// `reseed_interval` of 0 reseeds before every request.
+    pub fn new(
+        entropy: R,
+        personalization: &[u8],
+        reseed_interval: u32,
+    ) -> Result<Self, Error<R::Error>> {
+        let mut drbg = HmacDrbg {
+            entropy,
+            key: [0x00; OUTLEN],
+            v: [0x01; OUTLEN],
+            reseed_counter: 1,
+            reseed_interval,
+        };
+
+        let mut seed = [0u8; ENTROPY_LEN + NONCE_LEN];
+        drbg.entropy.read(&mut seed).map_err(Error::Entropy)?;
+        drbg.update(&[&seed, personalization]);
+        zero_volatile(&mut seed);
+
+        Ok(drbg)
+    }
+
+    /// Mix fresh entropy and the optional `additional_input` into the state
+    pub fn reseed(&mut self, additional_input: &[u8]) -> Result<(), Error<R::Error>> {
+        let mut entropy = [0u8; ENTROPY_LEN];
+        self.entropy.read(&mut entropy).map_err(Error::Entropy)?;
+        self.update(&[&entropy, additional_input]);
+        zero_volatile(&mut entropy);
+
+        self.reseed_counter = 1;
+        Ok(())
+    }
+
+    /// Fill `output` with pseudorandom bytes, optionally mixing in `additional_input`
+    pub fn generate(
+        &mut self,
+        output: &mut [u8],
+        additional_input: &[u8],
+    ) -> Result<(), Error<R::Error>> {
+        if output.len() > MAX_REQUEST_BYTES {
+            return Err(Error::RequestTooLarge);
+        }
+
+        let mut additional_input = additional_input;
+        if self.reseed_counter > self.reseed_interval {
+            self.reseed(additional_input)?;
+            additional_input = &[];
+        }
+
+        if !additional_input.is_empty() {
+            self.update(&[additional_input]);
+        }
+
+        for chunk in output.chunks_mut(OUTLEN) {
+            self.v = self.hmac(&[&self.v]);
+            chunk.copy_from_slice(&self.v[..chunk.len()]);
+        }
+
+        self.update(&[additional_input]);
+        self.reseed_counter = self.reseed_counter.saturating_add(1);
+        Ok(())
+    }
+
+    /// Wipe the DRBG state and release the entropy source
+    pub fn free(mut self) -> R {
+        self.wipe();
+        // Safe as `entropy` is the only field with drop glue and `self` is
+        // forgotten right after it is moved out
+        let entropy = unsafe { ptr::read(&self.entropy) };
+        mem::forget(self);
+        entropy
+    }
+}
+
+impl<R> HmacDrbg<R> {
+    /// HMAC_DRBG_Update, `provided` is the concatenation of its parts
+    fn update(&mut self, provided: &[&[u8]]) {
+        let provided_empty = provided.iter().all(|part| part.is_empty());
+
+        self.key = self.hmac_provided(0x00, provided);
+        self.v = self.hmac(&[&self.v]);
+
+        if !provided_empty {
+            self.key = self.hmac_provided(0x01, provided);
+            self.v = self.hmac(&[&self.v]);
+        }
+    }
+
+    /// HMAC(K, V || separator || provided)
+    fn hmac_provided(&self, separator: u8, provided: &[&[u8]]) -> [u8; OUTLEN] {
+        let mut mac = HmacSha256::new(&self.key);
+        mac.update(&self.v);
+        mac.update(&[separator]);
+        for part in provided {
+            mac.update(part);
+        }
+        mac.finalize()
+    }
+
+    fn hmac(&self, data: &[&[u8]]) -> [u8; OUTLEN] {
+        let mut mac = HmacSha256::new(&self.key);
+        for part in data {
+            mac.update(part);
+        }
+        mac.finalize()
+    }
+}
+
+impl<R> Wipe for HmacDrbg<R> {
+    fn wipe(&mut self) {
+        zero_volatile(&mut self.key);
+        zero_volatile(&mut self.v);
+        // Force a reseed should the state ever be used again
+        self.reseed_counter = 1;
+        self.reseed_interval = 0;
+    }
+}
+
+impl<R> Drop for HmacDrbg<R> {
+    fn drop(&mut self) {
+        self.wipe();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<R> Zeroize for HmacDrbg<R> {
+    fn zeroize(&mut self) {
+        self.wipe();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<R> ZeroizeOnDrop for HmacDrbg<R> {}
+
+#[cfg(feature = "rand_core")]
+impl<R: Read> RngCore for HmacDrbg<R> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest).unwrap();
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), RandError> {
+        for chunk in dest.chunks_mut(MAX_REQUEST_BYTES) {
+            self.generate(chunk, &[])
+                .map_err(|_| RandError::new(RandErrorKind::Unavailable, "entropy source failed"))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "rand_core")]
+impl<R: Read> CryptoRng for HmacDrbg<R> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Entropy source replaying a fixed byte string
+    struct ReplayTrng {
+        data: &'static [u8],
+        pos: usize,
+    }
+
+    impl ReplayTrng {
+        fn new(data: &'static [u8]) -> Self {
+            ReplayTrng { data, pos: 0 }
+        }
+    }
+
+    impl Read for ReplayTrng {
+        type Error = ();
+
+        fn read(&mut self, buffer: &mut [u8]) -> Result<(), ()> {
+            if self.data.len() - self.pos < buffer.len() {
+                return Err(());
+            }
+            buffer.copy_from_slice(&self.data[self.pos..self.pos + buffer.len()]);
+            self.pos += buffer.len();
+            Ok(())
+        }
+    }
+
+    // NIST CAVP HMAC_DRBG.rsp, SHA-256, no prediction resistance, COUNT = 0
+    const CAVP_SEED: [u8; 48] = hex!(
+        "ca851911349384bffe89de1cbdc46e6831e44d34a4fb935ee285dd14b71a7488"
+        "659ba96c601dc69fc902940805ec0ca8"
+    );
+
+    #[test]
+    fn test_hmac_drbg_cavp() {
+        let mut drbg =
+            HmacDrbg::new(ReplayTrng::new(&CAVP_SEED), &[], DEFAULT_RESEED_INTERVAL).unwrap();
+
+        let mut output = [0u8; 128];
+        drbg.generate(&mut output, &[]).unwrap();
+        drbg.generate(&mut output, &[]).unwrap();
+        assert_eq!(
+            output[..],
+            hex!(
+                "e528e9abf2dece54d47c7e75e5fe302149f817ea9fb4bee6f4199697d04d5b89"
+                "d54fbb978a15b5c443c9ec21036d2460b6f73ebad0dc2aba6e624abf07745bc1"
+                "07694bb7547bb0995f70de25d6b29e2d3011bb19d27676c07162c8b5ccde0668"
+                "961df86803482cb37ed6d5c0bb8d50cf1f50d476aa0458bdaba806f48be9dcb8"
+            )[..]
+        );
+    }
+
+    #[test]
+    fn test_hmac_drbg_reseed_interval() {
+        static ENTROPY: [u8; 112] = [0x42; 112];
+        let mut drbg = HmacDrbg::new(ReplayTrng::new(&ENTROPY), b"serial", 2).unwrap();
+
+        let mut output = [0u8; 40];
+        drbg.generate(&mut output, &[]).unwrap();
+        assert_eq!(drbg.entropy.pos, 48);
+        drbg.generate(&mut output, b"extra").unwrap();
+        assert_eq!(drbg.entropy.pos, 48);
+
+        // The third request is past the interval and reseeds first
+        drbg.generate(&mut output, b"extra").unwrap();
+        assert_eq!(drbg.entropy.pos, 80);
+        assert_eq!(
+            output[..],
+            hex!(
+                "1c71737c3a701b6cae36e0afccb7afb9dbe87db4c23a57f1e7f2cf2c794d0376"
+                "0f6cedd88634db61"
+            )[..]
+        );
+
+        drbg.reseed(&[]).unwrap();
+        assert_eq!(drbg.entropy.pos, 112);
+        assert_eq!(drbg.generate(&mut output, &[]), Ok(()));
+        assert_eq!(drbg.generate(&mut output, &[]), Ok(()));
+
+        // The entropy source is exhausted now
+        assert_eq!(drbg.generate(&mut output, &[]), Err(Error::Entropy(())));
+    }
+
+    #[test]
+    fn test_hmac_drbg_errors() {
+        assert!(HmacDrbg::new(ReplayTrng::new(&CAVP_SEED[..47]), &[], 1).is_err());
+
+        let mut drbg = HmacDrbg::new(ReplayTrng::new(&CAVP_SEED), &[], 1).unwrap();
+        let mut output = [0u8; MAX_REQUEST_BYTES + 1];
+        assert_eq!(drbg.generate(&mut output, &[]), Err(Error::RequestTooLarge));
+        assert_eq!(drbg.generate(&mut output[..MAX_REQUEST_BYTES], &[]), Ok(()));
+    }
+
+    #[test]
+    fn test_hmac_drbg_free() {
+        let drbg = HmacDrbg::new(ReplayTrng::new(&CAVP_SEED), &[], 1).unwrap();
+        let trng = drbg.free();
+        assert_eq!(trng.pos, 48);
+    }
+
+    #[cfg(feature = "rand_core")]
+    #[test]
+    fn test_hmac_drbg_rng_core() {
+        let mut drbg =
+            HmacDrbg::new(ReplayTrng::new(&CAVP_SEED), &[], DEFAULT_RESEED_INTERVAL).unwrap();
+        let mut expected =
+            HmacDrbg::new(ReplayTrng::new(&CAVP_SEED), &[], DEFAULT_RESEED_INTERVAL).unwrap();
+
+        let mut output = [0u8; 128];
+        let mut expected_output = [0u8; 128];
+        drbg.fill_bytes(&mut output);
+        expected.generate(&mut expected_output, &[]).unwrap();
+        assert_eq!(output[..], expected_output[..]);
+
+        let mut bytes = [0u8; 4];
+        expected.generate(&mut bytes, &[]).unwrap();
+        assert_eq!(drbg.next_u32(), u32::from_le_bytes(bytes));
+
+        let mut drbg = HmacDrbg::new(ReplayTrng::new(&CAVP_SEED), &[], 0).unwrap();
+        assert!(drbg.try_fill_bytes(&mut output).is_err());
+    }
+}
@@ -0,0 +1,349 @@
+use core::ops::Range;
+use core::ptr;
+
+use crypto::p256::{self, ProjectivePoint, N, U256};
+use crypto::{Error, Sha256, SHA256_BLOCKSIZE_BYTES, SHA256_STATE_LEN};
+
+/// Length of a P-256 field element or scalar in bytes
+pub const P256_SCALAR_LEN: usize = 32;
+
+/// A P-256 public key used to verify ECDSA signatures
+///
+/// Verification only handles public data and is not constant time.
+#[derive(Clone, Copy)]
+pub struct PublicKey {
+    x: U256,
+    y: U256,
+}
+
+impl PublicKey {
+    /// Create a key from its affine coordinates, the point has to be on the curve
+    pub fn from_coordinates(
+        x: &[u8; P256_SCALAR_LEN],
+        y: &[u8; P256_SCALAR_LEN],
+    ) -> Result<Self, Error> {
+        let x = p256::from_be_bytes(x);
+        let y = p256::from_be_bytes(y);
+        if !p256::is_on_curve(&x, &y) {
+            return Err(Error::InvalidEncoding);
+        }
+
+        Ok(PublicKey { x, y })
+    }
+
+    /// Create a key from a SEC1 encoded point, either uncompressed (65 bytes,
+    /// `0x04` tag) or compressed (33 bytes, `0x02` or `0x03` tag)
+    pub fn from_sec1_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut x = [0u8; P256_SCALAR_LEN];
+        match (bytes.first(), bytes.len()) {
+            (Some(0x04), 65) => {
+                let mut y = [0u8; P256_SCALAR_LEN];
+                x.copy_from_slice(&bytes[1..33]);
+                y.copy_from_slice(&bytes[33..]);
+                Self::from_coordinates(&x, &y)
+            }
+            (Some(&tag @ 0x02), 33) | (Some(&tag @ 0x03), 33) => {
+                x.copy_from_slice(&bytes[1..]);
+                let x = p256::from_be_bytes(&x);
+                let y = p256::decompress(&x, tag == 0x03).ok_or(Error::InvalidEncoding)?;
+                Ok(PublicKey { x, y })
+            }
+            _ => Err(Error::InvalidEncoding),
+        }
+    }
+
+    /// Verify a signature over `message`, which is hashed with the ROM `Sha256`
+    pub fn verify(&self, message: &[u8], signature: &Signature) -> Result<(), Error> {
+        let mut hasher = Sha256::new();
+        hasher.update(message);
+        self.verify_prehash(&hasher.finalize(), signature)
+    }
+
+    /// Verify a signature over an already computed SHA-256 digest
+    pub fn verify_prehash(
+        &self,
+        digest: &[u8; SHA256_STATE_LEN * 4],
+        signature: &Signature,
+    ) -> Result<(), Error> {
+        let r = p256::from_be_bytes(&signature.r);
+        let s = p256::from_be_bytes(&signature.s);
+        if !N.is_valid_nonzero(&r) || !N.is_valid_nonzero(&s) {
+            return Err(Error::VerificationFailed);
+        }
+
+        // The digest is exactly as long as the group order, a single
+        // subtraction reduces it
+        let e = N.reduce(&p256::from_be_bytes(digest));
+
+        let s_inv = N.invert(&N.enter_mont(&s));
+        let u1 = N.leave_mont(&N.mul(&N.enter_mont(&e), &s_inv));
+        let u2 = N.leave_mont(&N.mul(&N.enter_mont(&r), &s_inv));
+
+        let point = p256::double_scalar_mul(
+            &u1,
+            &ProjectivePoint::generator(),
+            &u2,
+            &ProjectivePoint::from_affine(&self.x, &self.y),
+        );
+
+        match point.affine_x() {
+            Some(x) if N.reduce(&x) == r => Ok(()),
+            _ => Err(Error::VerificationFailed),
+        }
+    }
+
+    /// Verify a signature over the contents of memory mapped flash in `range`
+    ///
+    /// The range is read with volatile byte reads, so it may start at address
+    /// zero, for example to check the secure application image.
+    ///
+    /// # Safety
+    ///
+    /// `range` has to lie entirely within readable memory.
+    pub unsafe fn verify_flash(
+        &self,
+        range: Range<usize>,
+        signature: &Signature,
+    ) -> Result<(), Error> {
+        if range.end < range.start {
+            return Err(Error::InvalidLength);
+        }
+
+        let mut hasher = Sha256::new();
+        let mut chunk = [0u8; SHA256_BLOCKSIZE_BYTES];
+        let mut address = range.start;
+        while address < range.end {
+            let len = (range.end - address).min(chunk.len());
+            for (i, b) in chunk[..len].iter_mut().enumerate() {
+                *b = ptr::read_volatile((address + i) as *const u8);
+            }
+            hasher.update(&chunk[..len]);
+            address += len;
+        }
+
+        self.verify_prehash(&hasher.finalize(), signature)
+    }
+}
+
+/// An ECDSA P-256 signature
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Signature {
+    r: [u8; P256_SCALAR_LEN],
+    s: [u8; P256_SCALAR_LEN],
+}
+
+impl Signature {
+    /// Create a signature from the fixed size `r || s` encoding
+    pub fn from_bytes(bytes: &[u8; 2 * P256_SCALAR_LEN]) -> Self {
+        let mut r = [0u8; P256_SCALAR_LEN];
+        let mut s = [0u8; P256_SCALAR_LEN];
+        r.copy_from_slice(&bytes[..P256_SCALAR_LEN]);
+        s.copy_from_slice(&bytes[P256_SCALAR_LEN..]);
+        Signature { r, s }
+    }
+
+    /// Create a signature from its ASN.1 DER encoding, rejecting anything
+    /// that is not strictly DER
+    pub fn from_der(bytes: &[u8]) -> Result<Self, Error> {
+        // SEQUENCE with a short form length, two INTEGERs never need more
+        if bytes.len() < 2 || bytes[0] != 0x30 || bytes[1] as usize != bytes.len() - 2 {
+            return Err(Error::InvalidEncoding);
+        }
+
+        let (r, rest) = der_integer(&bytes[2..])?;
+        let (s, rest) = der_integer(rest)?;
+        if !rest.is_empty() {
+            return Err(Error::InvalidEncoding);
+        }
+
+        Ok(Signature { r, s })
+    }
+
+    /// The fixed size `r || s` encoding
+    pub fn to_bytes(&self) -> [u8; 2 * P256_SCALAR_LEN] {
+        let mut ret = [0u8; 2 * P256_SCALAR_LEN];
+        ret[..P256_SCALAR_LEN].copy_from_slice(&self.r);
+        ret[P256_SCALAR_LEN..].copy_from_slice(&self.s);
+        ret
+    }
+}
+
+/// Parse a non-negative, minimally encoded DER INTEGER of up to 32 bytes
+fn der_integer(bytes: &[u8]) -> Result<([u8; P256_SCALAR_LEN], &[u8]), Error> {
+    if bytes.len() < 2 || bytes[0] != 0x02 {
+        return Err(Error::InvalidEncoding);
+    }
+
+    let len = bytes[1] as usize;
+    if len == 0 || len > bytes.len() - 2 {
+        return Err(Error::InvalidEncoding);
+    }
+
+    let mut value = &bytes[2..2 + len];
+    if value[0] & 0x80 != 0 {
+        return Err(Error::InvalidEncoding);
+    }
+    if value[0] == 0 && value.len() > 1 {
+        if value[1] & 0x80 == 0 {
+            return Err(Error::InvalidEncoding);
+        }
+        value = &value[1..];
+    }
+    if value.len() > P256_SCALAR_LEN {
+        return Err(Error::InvalidEncoding);
+    }
+
+    let mut ret = [0u8; P256_SCALAR_LEN];
+    ret[P256_SCALAR_LEN - value.len()..].copy_from_slice(value);
+    Ok((ret, &bytes[2 + len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wycheproof ecdsa_secp256r1_sha256_test.json packed with blobby, each
+    /// vector is five blobs: key x, key y, message, DER signature, and a
+    /// single byte that is 1 if the signature is valid
+    const WYCHEPROOF: &[u8] = include_bytes!("test_vectors/wycheproof_p256_sha256.blb");
+
+    /// Iterator over the blobs of a blobby file
+    struct Blobs<'a> {
+        data: &'a [u8],
+        pos: usize,
+        dedup: [&'a [u8]; 64],
+    }
+
+    impl<'a> Blobs<'a> {
+        fn new(data: &'a [u8]) -> Self {
+            let mut blobs = Blobs {
+                data,
+                pos: 0,
+                dedup: [&[]; 64],
+            };
+            let dedup_len = blobs.read_vlq();
+            for i in 0..dedup_len {
+                let len = blobs.read_vlq();
+                blobs.dedup[i] = blobs.read_blob(len);
+            }
+            blobs
+        }
+
+        fn read_vlq(&mut self) -> usize {
+            let mut val = 0usize;
+            loop {
+                let b = self.data[self.pos];
+                self.pos += 1;
+                val = (val << 7) | (b & 0x7f) as usize;
+                if b & 0x80 == 0 {
+                    return val;
+                }
+                val += 1;
+            }
+        }
+
+        fn read_blob(&mut self, len: usize) -> &'a [u8] {
+            let data = self.data;
+            self.pos += len;
+            &data[self.pos - len..self.pos]
+        }
+    }
+
+    impl<'a> Iterator for Blobs<'a> {
+        type Item = &'a [u8];
+
+        fn next(&mut self) -> Option<&'a [u8]> {
+            if self.pos == self.data.len() {
+                return None;
+            }
+
+            let val = self.read_vlq();
+            if val & 1 == 1 {
+                Some(self.dedup[val >> 1])
+            } else {
+                Some(self.read_blob(val >> 1))
+            }
+        }
+    }
+
+    /// Coordinates are stored as minimal big endian integers, with a
+    /// leading zero byte if the top bit is set
+    fn coordinate(bytes: &[u8]) -> [u8; P256_SCALAR_LEN] {
+        let bytes = &bytes[bytes.len().saturating_sub(P256_SCALAR_LEN)..];
+        let mut ret = [0u8; P256_SCALAR_LEN];
+        ret[P256_SCALAR_LEN - bytes.len()..].copy_from_slice(bytes);
+        ret
+    }
+
+    #[test]
+    fn test_ecdsa_p256_wycheproof() {
+        let mut blobs = Blobs::new(WYCHEPROOF);
+        let mut vectors = 0;
+
+        while let Some(x) = blobs.next() {
+            let y = blobs.next().unwrap();
+            let message = blobs.next().unwrap();
+            let der = blobs.next().unwrap();
+            let valid = blobs.next().unwrap() == [1];
+            vectors += 1;
+
+            let key = PublicKey::from_coordinates(&coordinate(x), &coordinate(y)).unwrap();
+            let result = Signature::from_der(der).and_then(|sig| key.verify(message, &sig));
+            assert_eq!(result.is_ok(), valid, "vector {}", vectors);
+        }
+
+        assert_eq!(vectors, 386);
+    }
+
+    // RFC 6979 A.2.5, P-256 with SHA-256 and message "sample"
+    const RFC6979_KEY: [u8; 65] = hex!(
+        "04
+         60fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6
+         7903fe1008b8bc99a41ae9e95628bc64f2f1b20c2d7e9f5177a3c294d4462299"
+    );
+    const RFC6979_SIG: [u8; 64] = hex!(
+        "efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716
+         f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8"
+    );
+
+    #[test]
+    fn test_ecdsa_p256_encodings() {
+        let key = PublicKey::from_sec1_bytes(&RFC6979_KEY).unwrap();
+        let sig = Signature::from_bytes(&RFC6979_SIG);
+        assert_eq!(sig.to_bytes()[..], RFC6979_SIG[..]);
+        assert_eq!(key.verify(b"sample", &sig), Ok(()));
+        assert_eq!(key.verify(b"samplf", &sig), Err(Error::VerificationFailed));
+
+        // y is odd, so the compressed form carries the 0x03 tag
+        let mut compressed = [0u8; 33];
+        compressed[0] = 0x03;
+        compressed[1..].copy_from_slice(&RFC6979_KEY[1..33]);
+        let key = PublicKey::from_sec1_bytes(&compressed).unwrap();
+        assert_eq!(key.verify(b"sample", &sig), Ok(()));
+
+        compressed[0] = 0x02;
+        let key = PublicKey::from_sec1_bytes(&compressed).unwrap();
+        assert_eq!(key.verify(b"sample", &sig), Err(Error::VerificationFailed));
+
+        let mut off_curve = RFC6979_KEY;
+        off_curve[64] ^= 1;
+        assert!(PublicKey::from_sec1_bytes(&off_curve).is_err());
+        assert!(PublicKey::from_sec1_bytes(&RFC6979_KEY[..64]).is_err());
+    }
+
+    #[test]
+    fn test_ecdsa_p256_verify_flash() {
+        let key = PublicKey::from_sec1_bytes(&RFC6979_KEY).unwrap();
+        let sig = Signature::from_bytes(&RFC6979_SIG);
+        let image = b"sample";
+        let start = image.as_ptr() as usize;
+
+        unsafe {
+            assert_eq!(key.verify_flash(start..start + image.len(), &sig), Ok(()));
+            assert_eq!(
+                key.verify_flash(start..start + image.len() - 1, &sig),
+                Err(Error::VerificationFailed)
+            );
+        }
+    }
+}
@@ -0,0 +1,332 @@
+#[cfg(feature = "aead")]
+use aead::consts::{U0, U12, U16};
+#[cfg(feature = "aead")]
+use aead::generic_array::GenericArray;
+#[cfg(feature = "aead")]
+use aead::{AeadCore, AeadInPlace, NewAead};
+
+#[cfg(not(test))]
+use crypto::{RomCryptoApi, GF128_WORDS};
+use crypto::{constant_time_eq, zero_volatile, Aes128, Aes128Cipher, Error, Wipe};
+use crypto::{AES128_KEY_BYTES, AES_BLOCKSIZE_BYTES};
+
+/// Length of the GCM nonce, only 96 bit nonces are supported
+pub const GCM_NONCE_LEN: usize = 12;
+/// Length of the GCM authentication tag
+pub const GCM_TAG_LEN: usize = 16;
+
+type Block = [u8; AES_BLOCKSIZE_BYTES];
+
+/// AES-128-GCM authenticated encryption (NIST SP 800-38D)
+///
+/// Both the block cipher and the GHASH multiplication by `H` are done with the
+/// CRYA ROM functions.
+#[derive(Clone)]
+pub struct Aes128Gcm<C: Aes128Cipher = Aes128> {
+    cipher: C,
+    h: Block,
+}
+
+impl Aes128Gcm {
+    pub fn new(key: &[u8; AES128_KEY_BYTES]) -> Self {
+        Aes128Gcm::with_cipher(Aes128::new(key))
+    }
+}
+
+impl<C: Aes128Cipher> Aes128Gcm<C> {
+    /// GCM on `cipher`, e.g. an `Aes128Ref` to a key in TrustRAM
+    pub fn with_cipher(cipher: C) -> Self {
+        let mut h = [0u8; AES_BLOCKSIZE_BYTES];
+        cipher.encrypt_block(&mut h);
+
+        Aes128Gcm { cipher, h }
+    }
+
+    /// Encrypt `buffer` in place and return the authentication tag over
+    /// `associated_data` and the ciphertext
+    pub fn encrypt_in_place_detached(
+        &self,
+        nonce: &[u8; GCM_NONCE_LEN],
+        associated_data: &[u8],
+        buffer: &mut [u8],
+    ) -> [u8; GCM_TAG_LEN] {
+        let j0 = Self::initial_counter(nonce);
+        self.apply_keystream(&j0, buffer);
+        self.compute_tag(&j0, associated_data, buffer)
+    }
+
+    /// Check `tag` over `associated_data` and the ciphertext in `buffer`, and
+    /// decrypt `buffer` in place if it is valid
+    ///
+    /// The buffer is left untouched when verification fails.
+    pub fn decrypt_in_place_detached(
+        &self,
+        nonce: &[u8; GCM_NONCE_LEN],
+        associated_data: &[u8],
+        buffer: &mut [u8],
+        tag: &[u8; GCM_TAG_LEN],
+    ) -> Result<(), Error> {
+        let j0 = Self::initial_counter(nonce);
+        let expected = self.compute_tag(&j0, associated_data, buffer);
+
+        if constant_time_eq(&expected, tag) {
+            self.apply_keystream(&j0, buffer);
+            Ok(())
+        } else {
+            Err(Error::VerificationFailed)
+        }
+    }
+
+    fn initial_counter(nonce: &[u8; GCM_NONCE_LEN]) -> Block {
+        let mut j0 = [0u8; AES_BLOCKSIZE_BYTES];
+        j0[..GCM_NONCE_LEN].copy_from_slice(nonce);
+        j0[AES_BLOCKSIZE_BYTES - 1] = 1;
+        j0
+    }
+
+    /// CTR mode starting at inc32(J0)
+    fn apply_keystream(&self, j0: &Block, buffer: &mut [u8]) {
+        let mut counter = *j0;
+
+        for chunk in buffer.chunks_mut(AES_BLOCKSIZE_BYTES) {
+            inc32(&mut counter);
+
+            let mut keystream = counter;
+            self.cipher.encrypt_block(&mut keystream);
+            for (b, k) in chunk.iter_mut().zip(keystream.iter()) {
+                *b ^= k;
+            }
+        }
+    }
+
+    fn compute_tag(&self, j0: &Block, associated_data: &[u8], ciphertext: &[u8]) -> Block {
+        let mut y = [0u8; AES_BLOCKSIZE_BYTES];
+        self.ghash_update(&mut y, associated_data);
+        self.ghash_update(&mut y, ciphertext);
+
+        let mut lengths = [0u8; AES_BLOCKSIZE_BYTES];
+        lengths[..8].copy_from_slice(&((associated_data.len() as u64) * 8).to_be_bytes());
+        lengths[8..].copy_from_slice(&((ciphertext.len() as u64) * 8).to_be_bytes());
+        self.ghash_update(&mut y, &lengths);
+
+        let mut tag = *j0;
+        self.cipher.encrypt_block(&mut tag);
+        for (t, y) in tag.iter_mut().zip(y.iter()) {
+            *t ^= y;
+        }
+        tag
+    }
+
+    /// Absorb `data` into the GHASH accumulator `y`, zero padding the last block
+    fn ghash_update(&self, y: &mut Block, data: &[u8]) {
+        for chunk in data.chunks(AES_BLOCKSIZE_BYTES) {
+            for (y, d) in y.iter_mut().zip(chunk.iter()) {
+                *y ^= d;
+            }
+            *y = gf128_mult(y, &self.h);
+        }
+    }
+}
+
+impl<C: Aes128Cipher> Wipe for Aes128Gcm<C> {
+    fn wipe(&mut self) {
+        self.cipher.wipe_key();
+        zero_volatile(&mut self.h);
+    }
+}
+
+/// Increment the rightmost 32 bits of the counter block modulo 2^32
+fn inc32(counter: &mut Block) {
+    let mut ctr = [0u8; 4];
+    ctr.copy_from_slice(&counter[AES_BLOCKSIZE_BYTES - 4..]);
+    let ctr = u32::from_be_bytes(ctr).wrapping_add(1);
+    counter[AES_BLOCKSIZE_BYTES - 4..].copy_from_slice(&ctr.to_be_bytes());
+}
+
+/// The ROM works on the blocks in GCM bit order as they are laid out in
+/// memory, so the words are only a reinterpretation of the bytes.
+#[cfg(not(test))]
+fn gf128_mult(x: &Block, y: &Block) -> Block {
+    let mut block1 = [0u32; GF128_WORDS];
+    let mut block2 = [0u32; GF128_WORDS];
+    for (i, (a, b)) in x.chunks_exact(4).zip(y.chunks_exact(4)).enumerate() {
+        block1[i] = u32::from_ne_bytes([a[0], a[1], a[2], a[3]]);
+        block2[i] = u32::from_ne_bytes([b[0], b[1], b[2], b[3]]);
+    }
+
+    let mut product = [0u32; GF128_WORDS];
+    let rom_api = RomCryptoApi::api_table();
+    rom_api.gf128_mult(&block1, &block2, &mut product);
+
+    let mut ret = [0u8; AES_BLOCKSIZE_BYTES];
+    for (chunk, word) in ret.chunks_exact_mut(4).zip(product.iter()) {
+        chunk.copy_from_slice(&word.to_ne_bytes());
+    }
+    ret
+}
+
+#[cfg(test)]
+fn gf128_mult(x: &Block, y: &Block) -> Block {
+    const R: u128 = 0xe1 << 120;
+
+    let x = u128::from_be_bytes(*x);
+    let mut v = u128::from_be_bytes(*y);
+    let mut z = 0u128;
+
+    for i in 0..128 {
+        if (x >> (127 - i)) & 1 == 1 {
+            z ^= v;
+        }
+        v = if v & 1 == 1 { (v >> 1) ^ R } else { v >> 1 };
+    }
+
+    z.to_be_bytes()
+}
+
+#[cfg(feature = "aead")]
+impl NewAead for Aes128Gcm {
+    type KeySize = U16;
+
+    fn new(key: &GenericArray<u8, U16>) -> Self {
+        let mut raw_key = [0u8; AES128_KEY_BYTES];
+        raw_key.copy_from_slice(key);
+        Aes128Gcm::new(&raw_key)
+    }
+}
+
+#[cfg(feature = "aead")]
+impl<C: Aes128Cipher> AeadCore for Aes128Gcm<C> {
+    type NonceSize = U12;
+    type TagSize = U16;
+    type CiphertextOverhead = U0;
+}
+
+#[cfg(feature = "aead")]
+impl<C: Aes128Cipher> AeadInPlace for Aes128Gcm<C> {
+    fn encrypt_in_place_detached(
+        &self,
+        nonce: &GenericArray<u8, U12>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<GenericArray<u8, U16>, aead::Error> {
+        let mut raw_nonce = [0u8; GCM_NONCE_LEN];
+        raw_nonce.copy_from_slice(nonce);
+        let tag = Aes128Gcm::encrypt_in_place_detached(self, &raw_nonce, associated_data, buffer);
+        Ok(GenericArray::clone_from_slice(&tag))
+    }
+
+    fn decrypt_in_place_detached(
+        &self,
+        nonce: &GenericArray<u8, U12>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+        tag: &GenericArray<u8, U16>,
+    ) -> Result<(), aead::Error> {
+        let mut raw_nonce = [0u8; GCM_NONCE_LEN];
+        raw_nonce.copy_from_slice(nonce);
+        let mut raw_tag = [0u8; GCM_TAG_LEN];
+        raw_tag.copy_from_slice(tag);
+        Aes128Gcm::decrypt_in_place_detached(self, &raw_nonce, associated_data, buffer, &raw_tag)
+            .map_err(|_| aead::Error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::Aes128Ref;
+
+    const KEY: [u8; 16] = hex!("feffe9928665731c6d6a8f9467308308");
+    const NONCE: [u8; 12] = hex!("cafebabefacedbaddecaf888");
+    const PLAINTEXT: [u8; 64] = hex!(
+        "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72"
+        "1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b391aafd255"
+    );
+    const CIPHERTEXT: [u8; 64] = hex!(
+        "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e"
+        "21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091473f5985"
+    );
+    const AAD: [u8; 20] = hex!("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+
+    // Test cases 1 to 4 from the GCM specification (McGrew, Viega)
+    #[test]
+    fn test_aes128_gcm_encrypt() {
+        let gcm = Aes128Gcm::new(&[0u8; 16]);
+        let mut buffer = [0u8; 0];
+        let tag = gcm.encrypt_in_place_detached(&[0u8; 12], &[], &mut buffer);
+        assert_eq!(tag, hex!("58e2fccefa7e3061367f1d57a4e7455a"));
+
+        let mut buffer = [0u8; 16];
+        let tag = gcm.encrypt_in_place_detached(&[0u8; 12], &[], &mut buffer);
+        assert_eq!(buffer, hex!("0388dace60b6a392f328c2b971b2fe78"));
+        assert_eq!(tag, hex!("ab6e47d42cec13bdf53a67b21257bddf"));
+
+        let gcm = Aes128Gcm::new(&KEY);
+        let mut buffer = PLAINTEXT;
+        let tag = gcm.encrypt_in_place_detached(&NONCE, &[], &mut buffer);
+        assert_eq!(buffer[..], CIPHERTEXT[..]);
+        assert_eq!(tag, hex!("4d5c2af327cd64a62cf35abd2ba6fab4"));
+
+        let mut buffer = [0u8; 60];
+        buffer.copy_from_slice(&PLAINTEXT[..60]);
+        let tag = gcm.encrypt_in_place_detached(&NONCE, &AAD, &mut buffer);
+        assert_eq!(buffer[..], CIPHERTEXT[..60]);
+        assert_eq!(tag, hex!("5bc94fbc3221a5db94fae95ae7121a47"));
+    }
+
+    #[test]
+    fn test_aes128_gcm_decrypt() {
+        let gcm = Aes128Gcm::new(&KEY);
+        let tag = hex!("5bc94fbc3221a5db94fae95ae7121a47");
+
+        let mut buffer = [0u8; 60];
+        buffer.copy_from_slice(&CIPHERTEXT[..60]);
+        assert_eq!(gcm.decrypt_in_place_detached(&NONCE, &AAD, &mut buffer, &tag), Ok(()));
+        assert_eq!(buffer[..], PLAINTEXT[..60]);
+
+        let mut bad_tag = tag;
+        bad_tag[0] ^= 0x80;
+        buffer.copy_from_slice(&CIPHERTEXT[..60]);
+        assert_eq!(
+            gcm.decrypt_in_place_detached(&NONCE, &AAD, &mut buffer, &bad_tag),
+            Err(Error::VerificationFailed)
+        );
+        assert_eq!(buffer[..], CIPHERTEXT[..60]);
+
+        assert_eq!(
+            gcm.decrypt_in_place_detached(&NONCE, &AAD[1..], &mut buffer, &tag),
+            Err(Error::VerificationFailed)
+        );
+    }
+
+    #[cfg(feature = "aead")]
+    #[test]
+    fn test_aes128_gcm_aead_traits() {
+        let gcm = <Aes128Gcm as NewAead>::new(GenericArray::from_slice(&KEY));
+        let nonce = GenericArray::from_slice(&NONCE);
+
+        let mut buffer = [0u8; 60];
+        buffer.copy_from_slice(&PLAINTEXT[..60]);
+        let tag = AeadInPlace::encrypt_in_place_detached(&gcm, nonce, &AAD, &mut buffer).unwrap();
+        assert_eq!(buffer[..], CIPHERTEXT[..60]);
+        assert_eq!(tag[..], hex!("5bc94fbc3221a5db94fae95ae7121a47")[..]);
+
+        AeadInPlace::decrypt_in_place_detached(&gcm, nonce, &AAD, &mut buffer, &tag).unwrap();
+        assert_eq!(buffer[..], PLAINTEXT[..60]);
+
+        assert!(AeadInPlace::decrypt_in_place_detached(&gcm, nonce, &[], &mut buffer, &tag).is_err());
+    }
+
+    #[test]
+    fn test_aes128_gcm_ref() {
+        let gcm = Aes128Gcm::with_cipher(Aes128Ref::new(&KEY));
+        let mut buffer = [0u8; 60];
+        buffer.copy_from_slice(&PLAINTEXT[..60]);
+        let tag = gcm.encrypt_in_place_detached(&NONCE, &AAD, &mut buffer);
+        assert_eq!(buffer[..], CIPHERTEXT[..60]);
+        assert_eq!(tag, hex!("5bc94fbc3221a5db94fae95ae7121a47"));
+
+        assert_eq!(gcm.decrypt_in_place_detached(&NONCE, &AAD, &mut buffer, &tag), Ok(()));
+        assert_eq!(buffer[..], PLAINTEXT[..60]);
+    }
+}
@@ -0,0 +1,131 @@
+//! HKDF-SHA256 key derivation (RFC 5869) on top of the ROM backed `HmacSha256`
+
+use crypto::{zero_volatile, Error, HmacSha256};
+use crypto::SHA256_STATE_LEN;
+
+/// Length of the pseudorandom key produced by `extract`
+pub const PRK_LEN: usize = SHA256_STATE_LEN * 4;
+
+/// Maximum number of bytes `expand` can produce
+pub const MAX_OKM_LEN: usize = 255 * PRK_LEN;
+
+/// Extract a pseudorandom key from the input keying material `ikm`
+///
+/// An empty `salt` is the same as a salt of `PRK_LEN` zero bytes.
+pub fn extract(salt: &[u8], ikm: &[u8]) -> [u8; PRK_LEN] {
+    let mut mac = HmacSha256::new(salt);
+    mac.update(ikm);
+    mac.finalize()
+}
+
+/// Expand `prk` into `okm`, bound to the context and application specific `info`
+///
+/// Fails if more than `MAX_OKM_LEN` bytes are requested.
+pub fn expand(prk: &[u8], info: &[u8], okm: &mut [u8]) -> Result<(), Error> {
+    if okm.len() > MAX_OKM_LEN {
+        return Err(Error::InvalidLength);
+    }
+
+    let keyed_mac = HmacSha256::new(prk);
+    let mut t = [0u8; PRK_LEN];
+
+    for (i, chunk) in okm.chunks_mut(PRK_LEN).enumerate() {
+        let mut mac = keyed_mac.clone();
+        if i > 0 {
+            mac.update(&t);
+        }
+        mac.update(info);
+        mac.update(&[i as u8 + 1]);
+        t = mac.finalize();
+
+        chunk.copy_from_slice(&t[..chunk.len()]);
+    }
+
+    zero_volatile(&mut t);
+    Ok(())
+}
+
+/// Extract and expand in one step
+pub fn derive(salt: &[u8], ikm: &[u8], info: &[u8], okm: &mut [u8]) -> Result<(), Error> {
+    let mut prk = extract(salt, ikm);
+    let ret = expand(&prk, info, okm);
+    zero_volatile(&mut prk);
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 5869 appendix A.1
+    #[test]
+    fn test_hkdf_rfc5869_basic() {
+        let prk = extract(&hex!("000102030405060708090a0b0c"), &[0x0b; 22]);
+        assert_eq!(
+            prk,
+            hex!("077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5")
+        );
+
+        let mut okm = [0u8; 42];
+        expand(&prk, &hex!("f0f1f2f3f4f5f6f7f8f9"), &mut okm).unwrap();
+        assert_eq!(
+            okm[..],
+            hex!(
+                "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf"
+                "34007208d5b887185865"
+            )[..]
+        );
+    }
+
+    // RFC 5869 appendix A.2
+    #[test]
+    fn test_hkdf_rfc5869_long() {
+        let mut ikm = [0u8; 80];
+        let mut salt = [0u8; 80];
+        let mut info = [0u8; 80];
+        for i in 0..80 {
+            ikm[i] = i as u8;
+            salt[i] = 0x60 + i as u8;
+            info[i] = 0xb0 + i as u8;
+        }
+
+        let prk = extract(&salt, &ikm);
+        assert_eq!(
+            prk,
+            hex!("06a6b88c5853361a06104c9ceb35b45cef760014904671014a193f40c15fc244")
+        );
+
+        let mut okm = [0u8; 82];
+        expand(&prk, &info, &mut okm).unwrap();
+        assert_eq!(
+            okm[..],
+            hex!(
+                "b11e398dc80327a1c8e7f78c596a49344f012eda2d4efad8a050cc4c19afa97c"
+                "59045a99cac7827271cb41c65e590e09da3275600c2f09b8367793a9aca3db71"
+                "cc30c58179ec3e87c14c01d5c1f3434f1d87"
+            )[..]
+        );
+    }
+
+    // RFC 5869 appendix A.3
+    #[test]
+    fn test_hkdf_rfc5869_empty_salt_info() {
+        let mut okm = [0u8; 42];
+        derive(&[], &[0x0b; 22], &[], &mut okm).unwrap();
+        assert_eq!(
+            okm[..],
+            hex!(
+                "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d"
+                "9d201395faa4b61a96c8"
+            )[..]
+        );
+    }
+
+    #[test]
+    fn test_hkdf_output_too_long() {
+        let prk = [0u8; PRK_LEN];
+        let mut okm = [0u8; MAX_OKM_LEN + 1];
+        assert_eq!(expand(&prk, &[], &mut okm), Err(Error::InvalidLength));
+        assert_eq!(expand(&prk, &[], &mut okm[..MAX_OKM_LEN]), Ok(()));
+    }
+}
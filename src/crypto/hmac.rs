@@ -0,0 +1,160 @@
+use crypto::{constant_time_eq, zero_volatile, Error, Sha256, Wipe};
+use crypto::{SHA256_BLOCKSIZE_BYTES, SHA256_STATE_LEN};
+
+const IPAD: u8 = 0x36;
+const OPAD: u8 = 0x5c;
+
+/// HMAC-SHA256 (RFC 2104) using the ROM backed `Sha256`
+///
+/// Only the outer padded key is kept around next to the inner hasher, which
+/// saves a second copy of the 256 byte ROM scratch buffer.
+#[derive(Clone)]
+pub struct HmacSha256 {
+    inner: Sha256,
+    outer_key: [u8; SHA256_BLOCKSIZE_BYTES],
+}
+
+impl HmacSha256 {
+    /// Create a new MAC instance, keys longer than the block size are hashed first
+    pub fn new(key: &[u8]) -> Self {
+        let mut key_block = [0u8; SHA256_BLOCKSIZE_BYTES];
+        if key.len() > SHA256_BLOCKSIZE_BYTES {
+            let mut hasher = Sha256::new();
+            hasher.update(key);
+            key_block[..SHA256_STATE_LEN * 4].copy_from_slice(&hasher.finalize());
+        } else {
+            key_block[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner_key = [0u8; SHA256_BLOCKSIZE_BYTES];
+        let mut outer_key = [0u8; SHA256_BLOCKSIZE_BYTES];
+        for ((k, i), o) in key_block.iter().zip(inner_key.iter_mut()).zip(outer_key.iter_mut()) {
+            *i = k ^ IPAD;
+            *o = k ^ OPAD;
+        }
+
+        let mut inner = Sha256::new();
+        inner.update(&inner_key);
+
+        zero_volatile(&mut key_block);
+        zero_volatile(&mut inner_key);
+
+        HmacSha256 { inner, outer_key }
+    }
+
+    /// Feed `data` into the MAC, may be called any number of times
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    /// Return the 256 bit authentication tag
+    pub fn finalize(mut self) -> [u8; SHA256_STATE_LEN * 4] {
+        let mut inner_hash = self.inner.finalize_reset();
+
+        let mut outer = Sha256::new();
+        outer.update(&self.outer_key);
+        outer.update(&inner_hash);
+        zero_volatile(&mut inner_hash);
+        outer.finalize()
+    }
+
+    /// Check `tag` against the computed tag in constant time
+    pub fn verify(self, tag: &[u8]) -> Result<(), Error> {
+        if constant_time_eq(&self.finalize(), tag) {
+            Ok(())
+        } else {
+            Err(Error::VerificationFailed)
+        }
+    }
+}
+
+impl Wipe for HmacSha256 {
+    fn wipe(&mut self) {
+        self.inner.wipe();
+        zero_volatile(&mut self.outer_key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hmac(key: &[u8], data: &[u8]) -> [u8; 32] {
+        let mut mac = HmacSha256::new(key);
+        mac.update(data);
+        mac.finalize()
+    }
+
+    // RFC 4231 section 4
+    #[test]
+    fn test_hmac_sha256_rfc4231() {
+        assert_eq!(
+            hmac(&[0x0b; 20], b"Hi There")[..],
+            hex!("b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7")[..]
+        );
+
+        assert_eq!(
+            hmac(b"Jefe", b"what do ya want for nothing?")[..],
+            hex!("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")[..]
+        );
+
+        assert_eq!(
+            hmac(&[0xaa; 20], &[0xdd; 50])[..],
+            hex!("773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe")[..]
+        );
+
+        assert_eq!(
+            hmac(
+                &hex!("0102030405060708090a0b0c0d0e0f10111213141516171819"),
+                &[0xcd; 50]
+            )[..],
+            hex!("82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b")[..]
+        );
+
+        // Truncated to 128 bits
+        assert_eq!(
+            hmac(&[0x0c; 20], b"Test With Truncation")[..16],
+            hex!("a3b6167473100ee06e0c796c2955552b")[..]
+        );
+
+        assert_eq!(
+            hmac(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First")[..],
+            hex!("60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54")[..]
+        );
+
+        assert_eq!(
+            hmac(&[0xaa; 131], b"This is a test using a larger than block-size key and a larger than block-size data. The key needs to be hashed before being used by the HMAC algorithm.")[..],
+            hex!("9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2")[..]
+        );
+    }
+
+    #[test]
+    fn test_hmac_sha256_streaming() {
+        let mut mac = HmacSha256::new(b"Jefe");
+        mac.update(b"what do ya want ");
+        mac.update(b"for nothing?");
+        assert_eq!(
+            mac.finalize()[..],
+            hex!("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")[..]
+        );
+    }
+
+    #[test]
+    fn test_hmac_sha256_verify() {
+        let tag = hex!("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+
+        let mut mac = HmacSha256::new(b"Jefe");
+        mac.update(b"what do ya want for nothing?");
+        assert_eq!(mac.verify(&tag), Ok(()));
+
+        let mut bad_tag = tag;
+        bad_tag[31] ^= 1;
+        let mut mac = HmacSha256::new(b"Jefe");
+        mac.update(b"what do ya want for nothing?");
+        assert_eq!(mac.verify(&bad_tag), Err(Error::VerificationFailed));
+
+        let mut mac = HmacSha256::new(b"Jefe");
+        mac.update(b"what do ya want for nothing?");
+        assert_eq!(mac.verify(&tag[..16]), Err(Error::VerificationFailed));
+    }
+}
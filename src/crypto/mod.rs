@@ -1,9 +1,37 @@
-use core::iter;
-
-use arrayvec::ArrayVec;
-
-#[cfg(test)]
-use test_crypto::sha2::sha256_digest_block;
+use core::ptr;
+use core::sync::atomic::{self, Ordering};
+
+#[cfg(feature = "zeroize")]
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+mod aes;
+mod cbc;
+mod ccm;
+mod cmac;
+mod ctr;
+mod ecdsa;
+mod gcm;
+mod hmac;
+mod p256;
+mod pbkdf2;
+mod sha2;
+mod x25519;
+
+pub mod drbg;
+pub mod hkdf;
+pub mod selftest;
+
+pub use self::aes::*;
+pub use self::cbc::*;
+pub use self::ccm::*;
+pub use self::cmac::*;
+pub use self::ctr::*;
+pub use self::ecdsa::*;
+pub use self::gcm::*;
+pub use self::hmac::*;
+pub use self::pbkdf2::*;
+pub use self::sha2::*;
+pub use self::x25519::*;
 
 const ROM_CRYPTO_API: *const RomCryptoApi = 0x02001900 as *const RomCryptoApi;
 
@@ -11,6 +39,10 @@ const SHA256_STATE_LEN: usize = 8;
 const SHA256_RAM_BUFFER_LEN: usize = 64;
 const SHA256_BLOCKSIZE_BYTES: usize = 64;
 
+const AES_BLOCKSIZE_BYTES: usize = 16;
+const AES128_KEY_BYTES: usize = 16;
+const GF128_WORDS: usize = 4;
+
 #[repr(C)]
 struct RomCryptoApi {
     /// CRYA SHA function.
@@ -19,6 +51,26 @@ struct RomCryptoApi {
     /// data[In]: A pointer to a 512 bit data block
     /// ram_buf[In]: A pointer to a RAM buffer (256B needed for internal algorithm)
     crya_sha_process: extern "C" fn(hash_in_out: *mut u32, data: *const u8, ram_buf: *mut u32),
+    /// CRYA AES encryption function.
+    /// typedef void (*crya_aes_encrypt_t) (const uint8_t *keys, uint32_t key_len, const uint8_t *src, uint8_t *dst);
+    /// keys[In]: A pointer to the 128 bit key
+    /// key_len[In]: Number of 32 bit words in the key, 4 for AES-128
+    /// src[In]: A pointer to the 128 bit block to encrypt
+    /// dst[Out]: A pointer to the 128 bit encrypted block
+    crya_aes_encrypt: extern "C" fn(keys: *const u8, key_len: u32, src: *const u8, dst: *mut u8),
+    /// CRYA AES decryption function.
+    /// typedef void (*crya_aes_decrypt_t) (const uint8_t *keys, uint32_t key_len, const uint8_t *src, uint8_t *dst);
+    /// keys[In]: A pointer to the 128 bit key
+    /// key_len[In]: Number of 32 bit words in the key, 4 for AES-128
+    /// src[In]: A pointer to the 128 bit block to decrypt
+    /// dst[Out]: A pointer to the 128 bit decrypted block
+    crya_aes_decrypt: extern "C" fn(keys: *const u8, key_len: u32, src: *const u8, dst: *mut u8),
+    /// CRYA GF(2^128) multiplication function, as used by GCM.
+    /// typedef void (*crya_gf_mult128_t) (const uint32_t *block1, const uint32_t *block2, uint32_t *dst);
+    /// block1[In]: A pointer to the first 128 bit operand
+    /// block2[In]: A pointer to the second 128 bit operand
+    /// dst[Out]: A pointer to the 128 bit product
+    crya_gf_mult128: extern "C" fn(block1: *const u32, block2: *const u32, dst: *mut u32),
 }
 
 impl RomCryptoApi {
@@ -38,176 +90,132 @@ impl RomCryptoApi {
             ram_buf.as_mut_ptr(),
         );
     }
-}
-
-static H256: [u32; SHA256_STATE_LEN] = [
-    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
-];
-
-pub struct Sha256 {
-    state: [u32; SHA256_STATE_LEN],
-    ram_buffer: [u32; SHA256_RAM_BUFFER_LEN],
-    block_buffer: ArrayVec<[u8; SHA256_BLOCKSIZE_BYTES]>,
-    bit_length: u64,
-}
 
-impl Sha256 {
-    pub fn new() -> Self {
-        Sha256 {
-            state: H256,
-            ram_buffer: [0u32; SHA256_RAM_BUFFER_LEN],
-            block_buffer: ArrayVec::<[u8; SHA256_BLOCKSIZE_BYTES]>::new(),
-            bit_length: 0,
-        }
+    fn aes128_encrypt_block(
+        &self,
+        key: &[u8; AES128_KEY_BYTES],
+        src: &[u8; AES_BLOCKSIZE_BYTES],
+        dst: &mut [u8; AES_BLOCKSIZE_BYTES],
+    ) {
+        (self.crya_aes_encrypt)(
+            key.as_ptr(),
+            (AES128_KEY_BYTES / 4) as u32,
+            src.as_ptr(),
+            dst.as_mut_ptr(),
+        );
     }
 
-    #[cfg(not(test))]
-    fn process_block(&mut self, block: &[u8]) {
-        let rom_api = RomCryptoApi::api_table();
-        //println!("ROM I2C API address is: {:p}", rom_api);
-
-        rom_api.sha256_process_block(&mut self.state, block, &mut self.ram_buffer);
+    fn aes128_decrypt_block(
+        &self,
+        key: &[u8; AES128_KEY_BYTES],
+        src: &[u8; AES_BLOCKSIZE_BYTES],
+        dst: &mut [u8; AES_BLOCKSIZE_BYTES],
+    ) {
+        (self.crya_aes_decrypt)(
+            key.as_ptr(),
+            (AES128_KEY_BYTES / 4) as u32,
+            src.as_ptr(),
+            dst.as_mut_ptr(),
+        );
     }
 
-    #[cfg(test)]
-    fn process_block(&mut self, block: &[u8]) {
-        sha256_digest_block(&mut self.state, block);
+    fn gf128_mult(
+        &self,
+        block1: &[u32; GF128_WORDS],
+        block2: &[u32; GF128_WORDS],
+        dst: &mut [u32; GF128_WORDS],
+    ) {
+        (self.crya_gf_mult128)(block1.as_ptr(), block2.as_ptr(), dst.as_mut_ptr());
     }
+}
 
-    #[cfg(not(test))]
-    fn process_buffer(&mut self) {
-        let rom_api = RomCryptoApi::api_table();
-        //println!("ROM I2C API address is: {:p}", rom_api);
+/// Errors reported by the crypto primitives
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// A MAC or authentication tag did not match
+    VerificationFailed,
+    /// An input or output buffer has a length the algorithm does not support
+    InvalidLength,
+    /// The PKCS#7 padding of a decrypted message is malformed
+    InvalidPadding,
+    /// A public key or signature encoding is malformed, or the key is not a
+    /// usable curve point
+    InvalidEncoding,
+    /// A parameter is outside the range the algorithm allows, e.g. a zero
+    /// PBKDF2 iteration count
+    InvalidParameter,
+}
 
-        rom_api.sha256_process_block(&mut self.state, &self.block_buffer, &mut self.ram_buffer);
+/// Compare two byte slices without branching on their contents
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
     }
 
-    #[cfg(test)]
-    fn process_buffer(&mut self) {
-        sha256_digest_block(&mut self.state, &self.block_buffer);
+    let diff = a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    unsafe { ptr::read_volatile(&diff) == 0 }
+}
+
+/// Overwrite `buf` with zeros using volatile writes, so the compiler cannot
+/// drop the wipe as a dead store
+fn zero_volatile<T: Copy + Default>(buf: &mut [T]) {
+    for b in buf.iter_mut() {
+        unsafe { ptr::write_volatile(b, T::default()) };
     }
+    atomic::compiler_fence(Ordering::SeqCst);
+}
 
-    #[cfg(test)]
-    pub fn input(&mut self, data: &[u8]) {
-        self.bit_length += (data.len() as u64) * 8;
-        let mut data_in = data;
+/// Clear any key material or intermediate state held by a crypto type
+trait Wipe {
+    fn wipe(&mut self);
+}
 
-        if self.block_buffer.len() > 0 {
-            let mut remaining = self.block_buffer.capacity() - self.block_buffer.len();
-            if data_in.len() < remaining {
-                remaining = data_in.len();
+macro_rules! wipe_on_drop {
+    ($($Type:ident),+) => {
+        $(
+            impl Drop for $Type {
+                fn drop(&mut self) {
+                    self.wipe();
+                }
             }
 
-            let (left, right) = data_in.split_at(remaining);
-            self.block_buffer.extend(left.iter().cloned());
-            data_in = right;
-
-            if self.block_buffer.len() == SHA256_RAM_BUFFER_LEN {
-                self.process_buffer();
-                self.block_buffer.clear();
+            #[cfg(feature = "zeroize")]
+            impl Zeroize for $Type {
+                fn zeroize(&mut self) {
+                    self.wipe();
+                }
             }
 
-            if data_in.is_empty() {
-                return;
+            #[cfg(feature = "zeroize")]
+            impl ZeroizeOnDrop for $Type {}
+        )+
+    };
+    ($($Type:ident<$C:ident: $Bound:ident>),+) => {
+        $(
+            impl<$C: $Bound> Drop for $Type<$C> {
+                fn drop(&mut self) {
+                    self.wipe();
+                }
             }
-        }
 
-        let mut iter = data_in.chunks_exact(SHA256_BLOCKSIZE_BYTES);
-
-        for block in iter.by_ref() {
-            self.process_block(block);
-        }
-
-        self.block_buffer.extend(iter.remainder().iter().cloned());
-    }
-
-    #[cfg(test)]
-    pub fn result(mut self) -> [u8; SHA256_STATE_LEN * 4] {
-        self.block_buffer.push(0x80);
-
-        let remaining = self.block_buffer.capacity() - self.block_buffer.len();
-        if remaining < 8 {
-            self.block_buffer.extend(iter::repeat(0u8).take(remaining));
-
-            self.process_buffer();
-
-            self.block_buffer.clear();
-
-            self.block_buffer
-                .extend(iter::repeat(0u8).take(SHA256_BLOCKSIZE_BYTES - 8));
-        } else {
-            self.block_buffer
-                .extend(iter::repeat(0u8).take(remaining - 8));
-        }
-        self.block_buffer
-            .extend(self.bit_length.to_be_bytes().iter().cloned());
-
-        self.process_buffer();
-
-        let mut ret = [0u8; SHA256_STATE_LEN * 4];
-        let mut tmp = ArrayVec::<[u8; SHA256_STATE_LEN * 4]>::new();
-        for u32tmp in &self.state {
-            for u8tmp in &u32tmp.to_be_bytes() {
-                tmp.push(*u8tmp);
+            #[cfg(feature = "zeroize")]
+            impl<$C: $Bound> Zeroize for $Type<$C> {
+                fn zeroize(&mut self) {
+                    self.wipe();
+                }
             }
-        }
-        ret.copy_from_slice(&tmp);
-        ret
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    //#[test]
-    //fn works() {
-        //let rom_api = RomCryptoApi::api_table();
-        //println!("{:p}", rom_api.crya_sha_process);
-    //}
-
-    #[test]
-    fn test_sha256() {
-        let mut hasher = Sha256::new();
-        hasher.input(b"hello world");
-        let result = hasher.result();
-        assert_eq!(
-            result[..],
-            hex!("b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9")[..]
-        );
-
-        let mut hasher = Sha256::new();
-        hasher.input(b"hello");
-        hasher.input(b" world");
-        let result = hasher.result();
-        assert_eq!(
-            result[..],
-            hex!("b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9")[..]
-        );
-
-        let mut hasher = Sha256::new();
-        hasher.input(b"");
-        let result = hasher.result();
-        assert_eq!(
-            result[..],
-            hex!("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")[..]
-        );
-
-        let mut hasher = Sha256::new();
-        hasher.input(b"Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat. Duis aute irure dolor in reprehenderit in voluptate velit esse cillum dolore eu fugiat nulla pariatur. Excepteur sint occaecat cupidatat non proident, sunt in culpa qui officia deserunt mollit anim id est laborum.");
-        let result = hasher.result();
-        assert_eq!(
-            result[..],
-            hex!("2d8c2f6d978ca21712b5f6de36c9d31fa8e96a4fa5d8ff8b0188dfb9e7c171bb")[..]
-        );
-
-        let mut hasher = Sha256::new();
-        hasher.input(b"Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat. Duis aute irure dolor in reprehenderit in voluptate velit esse cillum dolore eu fugiat nulla pariatur. Excepteur sint occaecat cupidatat non proident, sunt in culpa qui officia deserunt mollit anim id est");
-        hasher.input(b" laborum.");
-        let result = hasher.result();
-        assert_eq!(
-            result[..],
-            hex!("2d8c2f6d978ca21712b5f6de36c9d31fa8e96a4fa5d8ff8b0188dfb9e7c171bb")[..]
-        );
-    }
+            #[cfg(feature = "zeroize")]
+            impl<$C: $Bound> ZeroizeOnDrop for $Type<$C> {}
+        )+
+    };
 }
+
+wipe_on_drop!(Sha256, Sha224, HmacSha256, Aes128, X25519KeyPair, X25519SharedSecret);
+wipe_on_drop!(
+    Aes128Gcm<C: Aes128Cipher>,
+    Aes128Cbc<C: Aes128Cipher>,
+    Aes128Ctr<C: Aes128Cipher>,
+    Aes128Cmac<C: Aes128Cipher>,
+    Aes128Ccm<C: Aes128Cipher>
+);
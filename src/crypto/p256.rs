@@ -0,0 +1,390 @@
+//! NIST P-256 arithmetic for signature verification
+//!
+//! Nothing in here runs in constant time, it must only ever be used on public
+//! values such as public keys, signatures and message digests.
+
+use core::cmp::Ordering;
+
+/// 256 bit integer as 32 bit limbs, least significant limb first
+pub type U256 = [u32; 8];
+
+const ZERO: U256 = [0; 8];
+const ONE: U256 = [1, 0, 0, 0, 0, 0, 0, 0];
+
+const B: U256 = [
+    0x27d2604b, 0x3bce3c3e, 0xcc53b0f6, 0x651d06b0, 0x769886bc, 0xb3ebbd55, 0xaa3a93e7, 0x5ac635d8,
+];
+const GX: U256 = [
+    0xd898c296, 0xf4a13945, 0x2deb33a0, 0x77037d81, 0x63a440f2, 0xf8bce6e5, 0xe12c4247, 0x6b17d1f2,
+];
+const GY: U256 = [
+    0x37bf51f5, 0xcbb64068, 0x6b315ece, 0x2bce3357, 0x7c0f9e16, 0x8ee7eb4a, 0xfe1a7f9b, 0x4fe342e2,
+];
+
+/// The field prime p
+pub static P: Modulus = Modulus {
+    m: [
+        0xffffffff, 0xffffffff, 0xffffffff, 0x00000000, 0x00000000, 0x00000000, 0x00000001,
+        0xffffffff,
+    ],
+    m_inv: 0x00000001,
+    r2: [
+        0x00000003, 0x00000000, 0xffffffff, 0xfffffffb, 0xfffffffe, 0xffffffff, 0xfffffffd,
+        0x00000004,
+    ],
+};
+
+/// The group order n
+pub static N: Modulus = Modulus {
+    m: [
+        0xfc632551, 0xf3b9cac2, 0xa7179e84, 0xbce6faad, 0xffffffff, 0xffffffff, 0x00000000,
+        0xffffffff,
+    ],
+    m_inv: 0xee00bc4f,
+    r2: [
+        0xbe79eea2, 0x83244c95, 0x49bd6fa6, 0x4699799c, 0x2b6bec59, 0x2845b239, 0xf3d95620,
+        0x66e12d94,
+    ],
+};
+
+pub fn from_be_bytes(bytes: &[u8; 32]) -> U256 {
+    let mut ret = ZERO;
+    for (limb, chunk) in ret.iter_mut().rev().zip(bytes.chunks_exact(4)) {
+        *limb = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    ret
+}
+
+pub fn is_zero(a: &U256) -> bool {
+    a.iter().all(|&limb| limb == 0)
+}
+
+fn cmp(a: &U256, b: &U256) -> Ordering {
+    a.iter().rev().cmp(b.iter().rev())
+}
+
+/// a + b, returning the carry out
+fn add(a: &U256, b: &U256) -> (U256, u32) {
+    let mut ret = ZERO;
+    let mut carry = 0u64;
+    for i in 0..8 {
+        let sum = a[i] as u64 + b[i] as u64 + carry;
+        ret[i] = sum as u32;
+        carry = sum >> 32;
+    }
+    (ret, carry as u32)
+}
+
+/// a - b, returning the borrow out
+fn sub(a: &U256, b: &U256) -> (U256, u32) {
+    let mut ret = ZERO;
+    let mut borrow = 0i64;
+    for i in 0..8 {
+        let diff = a[i] as i64 - b[i] as i64 + borrow;
+        ret[i] = diff as u32;
+        borrow = diff >> 32;
+    }
+    (ret, (borrow != 0) as u32)
+}
+
+/// An odd modulus close to 2^256 with the constants for Montgomery
+/// multiplication with R = 2^256
+pub struct Modulus {
+    m: U256,
+    /// -m^-1 mod 2^32
+    m_inv: u32,
+    /// R^2 mod m
+    r2: U256,
+}
+
+impl Modulus {
+    /// Check `0 < a < m`
+    pub fn is_valid_nonzero(&self, a: &U256) -> bool {
+        !is_zero(a) && cmp(a, &self.m) == Ordering::Less
+    }
+
+    /// Reduce a value below 2m
+    pub fn reduce(&self, a: &U256) -> U256 {
+        if cmp(a, &self.m) != Ordering::Less {
+            sub(a, &self.m).0
+        } else {
+            *a
+        }
+    }
+
+    pub fn add(&self, a: &U256, b: &U256) -> U256 {
+        let (sum, carry) = add(a, b);
+        if carry != 0 || cmp(&sum, &self.m) != Ordering::Less {
+            sub(&sum, &self.m).0
+        } else {
+            sum
+        }
+    }
+
+    pub fn sub(&self, a: &U256, b: &U256) -> U256 {
+        let (diff, borrow) = sub(a, b);
+        if borrow != 0 {
+            add(&diff, &self.m).0
+        } else {
+            diff
+        }
+    }
+
+    /// Montgomery multiplication, a * b * R^-1 mod m
+    pub fn mul(&self, a: &U256, b: &U256) -> U256 {
+        let mut t = [0u32; 10];
+
+        for &b_i in b.iter() {
+            let mut carry = 0u64;
+            for j in 0..8 {
+                let s = t[j] as u64 + a[j] as u64 * b_i as u64 + carry;
+                t[j] = s as u32;
+                carry = s >> 32;
+            }
+            let s = t[8] as u64 + carry;
+            t[8] = s as u32;
+            t[9] = (s >> 32) as u32;
+
+            let u = t[0].wrapping_mul(self.m_inv);
+            let s = t[0] as u64 + u as u64 * self.m[0] as u64;
+            let mut carry = s >> 32;
+            for j in 1..8 {
+                let s = t[j] as u64 + u as u64 * self.m[j] as u64 + carry;
+                t[j - 1] = s as u32;
+                carry = s >> 32;
+            }
+            let s = t[8] as u64 + carry;
+            t[7] = s as u32;
+            t[8] = t[9] + (s >> 32) as u32;
+            t[9] = 0;
+        }
+
+        let mut ret = ZERO;
+        ret.copy_from_slice(&t[..8]);
+        if t[8] != 0 || cmp(&ret, &self.m) != Ordering::Less {
+            ret = sub(&ret, &self.m).0;
+        }
+        ret
+    }
+
+    pub fn square(&self, a: &U256) -> U256 {
+        self.mul(a, a)
+    }
+
+    pub fn enter_mont(&self, a: &U256) -> U256 {
+        self.mul(a, &self.r2)
+    }
+
+    pub fn leave_mont(&self, a: &U256) -> U256 {
+        self.mul(a, &ONE)
+    }
+
+    /// Montgomery form of one
+    fn one(&self) -> U256 {
+        self.enter_mont(&ONE)
+    }
+
+    /// a^e for `a` in Montgomery form
+    pub fn pow(&self, a: &U256, e: &U256) -> U256 {
+        let mut ret = self.one();
+        for i in (0..256).rev() {
+            ret = self.square(&ret);
+            if (e[i / 32] >> (i % 32)) & 1 == 1 {
+                ret = self.mul(&ret, a);
+            }
+        }
+        ret
+    }
+
+    /// a^-1 for nonzero `a` in Montgomery form, by Fermat's little theorem
+    pub fn invert(&self, a: &U256) -> U256 {
+        let e = sub(&self.m, &[2, 0, 0, 0, 0, 0, 0, 0]).0;
+        self.pow(a, &e)
+    }
+}
+
+/// x^3 - 3x + b for `x` in Montgomery form
+fn curve_rhs(x: &U256) -> U256 {
+    let x3 = P.mul(&P.square(x), x);
+    let three_x = P.add(&P.add(x, x), x);
+    P.add(&P.sub(&x3, &three_x), &P.enter_mont(&B))
+}
+
+/// Check that the affine point (x, y) in normal form lies on the curve
+pub fn is_on_curve(x: &U256, y: &U256) -> bool {
+    if cmp(x, &P.m) != Ordering::Less || cmp(y, &P.m) != Ordering::Less {
+        return false;
+    }
+
+    let x = P.enter_mont(x);
+    let y = P.enter_mont(y);
+    P.square(&y) == curve_rhs(&x)
+}
+
+/// Recover the y coordinate with the given parity from x, both in normal form
+pub fn decompress(x: &U256, y_is_odd: bool) -> Option<U256> {
+    if cmp(x, &P.m) != Ordering::Less {
+        return None;
+    }
+
+    // p = 3 mod 4, so a square root is rhs^((p + 1) / 4)
+    let rhs = curve_rhs(&P.enter_mont(x));
+    let mut e = add(&P.m, &ONE).0;
+    for i in 0..8 {
+        e[i] = (e[i] >> 2) | (e.get(i + 1).cloned().unwrap_or(0) << 30);
+    }
+    let root = P.pow(&rhs, &e);
+    if P.square(&root) != rhs {
+        return None;
+    }
+
+    let y = P.leave_mont(&root);
+    if (y[0] & 1 == 1) == y_is_odd {
+        Some(y)
+    } else {
+        Some(P.sub(&ZERO, &y))
+    }
+}
+
+/// Point in Jacobian coordinates with Montgomery form field elements, z = 0
+/// is the point at infinity
+#[derive(Clone, Copy)]
+pub struct ProjectivePoint {
+    x: U256,
+    y: U256,
+    z: U256,
+}
+
+impl ProjectivePoint {
+    pub fn identity() -> Self {
+        ProjectivePoint {
+            x: ZERO,
+            y: ZERO,
+            z: ZERO,
+        }
+    }
+
+    pub fn generator() -> Self {
+        Self::from_affine(&GX, &GY)
+    }
+
+    /// Point from affine coordinates in normal form, which have to be on the curve
+    pub fn from_affine(x: &U256, y: &U256) -> Self {
+        ProjectivePoint {
+            x: P.enter_mont(x),
+            y: P.enter_mont(y),
+            z: P.one(),
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        is_zero(&self.z)
+    }
+
+    /// Affine x coordinate in normal form, `None` for the point at infinity
+    pub fn affine_x(&self) -> Option<U256> {
+        if self.is_identity() {
+            return None;
+        }
+
+        let z_inv = P.invert(&self.z);
+        Some(P.leave_mont(&P.mul(&self.x, &P.square(&z_inv))))
+    }
+
+    /// dbl-2001-b, using a = -3
+    pub fn double(&self) -> Self {
+        if self.is_identity() || is_zero(&self.y) {
+            return Self::identity();
+        }
+
+        let delta = P.square(&self.z);
+        let gamma = P.square(&self.y);
+        let beta = P.mul(&self.x, &gamma);
+        let t = P.mul(&P.sub(&self.x, &delta), &P.add(&self.x, &delta));
+        let alpha = P.add(&P.add(&t, &t), &t);
+
+        let beta4 = P.add(&P.add(&beta, &beta), &P.add(&beta, &beta));
+        let beta8 = P.add(&beta4, &beta4);
+        let x3 = P.sub(&P.square(&alpha), &beta8);
+
+        let yz = P.add(&self.y, &self.z);
+        let z3 = P.sub(&P.sub(&P.square(&yz), &gamma), &delta);
+
+        let gamma2 = P.square(&gamma);
+        let gamma2_4 = P.add(&P.add(&gamma2, &gamma2), &P.add(&gamma2, &gamma2));
+        let gamma2_8 = P.add(&gamma2_4, &gamma2_4);
+        let y3 = P.sub(&P.mul(&alpha, &P.sub(&beta4, &x3)), &gamma2_8);
+
+        ProjectivePoint {
+            x: x3,
+            y: y3,
+            z: z3,
+        }
+    }
+
+    /// add-1998-cmo-2, falling back to doubling for equal points
+    pub fn add(&self, other: &Self) -> Self {
+        if self.is_identity() {
+            return *other;
+        }
+        if other.is_identity() {
+            return *self;
+        }
+
+        let z1z1 = P.square(&self.z);
+        let z2z2 = P.square(&other.z);
+        let u1 = P.mul(&self.x, &z2z2);
+        let u2 = P.mul(&other.x, &z1z1);
+        let s1 = P.mul(&P.mul(&self.y, &other.z), &z2z2);
+        let s2 = P.mul(&P.mul(&other.y, &self.z), &z1z1);
+
+        if u1 == u2 {
+            return if s1 == s2 {
+                self.double()
+            } else {
+                Self::identity()
+            };
+        }
+
+        let h = P.sub(&u2, &u1);
+        let r = P.sub(&s2, &s1);
+        let hh = P.square(&h);
+        let hhh = P.mul(&h, &hh);
+        let v = P.mul(&u1, &hh);
+
+        let x3 = P.sub(&P.sub(&P.square(&r), &hhh), &P.add(&v, &v));
+        let y3 = P.sub(&P.mul(&r, &P.sub(&v, &x3)), &P.mul(&s1, &hhh));
+        let z3 = P.mul(&P.mul(&self.z, &other.z), &h);
+
+        ProjectivePoint {
+            x: x3,
+            y: y3,
+            z: z3,
+        }
+    }
+}
+
+/// a * A + b * B with Shamir's trick, scalars in normal form
+pub fn double_scalar_mul(
+    a: &U256,
+    point_a: &ProjectivePoint,
+    b: &U256,
+    point_b: &ProjectivePoint,
+) -> ProjectivePoint {
+    let sum = point_a.add(point_b);
+    let mut ret = ProjectivePoint::identity();
+
+    for i in (0..256).rev() {
+        ret = ret.double();
+        let bit_a = (a[i / 32] >> (i % 32)) & 1 == 1;
+        let bit_b = (b[i / 32] >> (i % 32)) & 1 == 1;
+        match (bit_a, bit_b) {
+            (true, true) => ret = ret.add(&sum),
+            (true, false) => ret = ret.add(point_a),
+            (false, true) => ret = ret.add(point_b),
+            (false, false) => (),
+        }
+    }
+
+    ret
+}
@@ -0,0 +1,106 @@
+use crypto::{zero_volatile, Error, HmacSha256};
+use crypto::SHA256_STATE_LEN;
+
+/// PBKDF2 (RFC 8018) with HMAC-SHA256 as the pseudorandom function
+///
+/// Fills all of `output` with derived key material. RFC 8018 requires at
+/// least one iteration, zero fails with `Error::InvalidParameter`.
+pub fn pbkdf2_hmac_sha256(
+    password: &[u8],
+    salt: &[u8],
+    iterations: u32,
+    output: &mut [u8],
+) -> Result<(), Error> {
+    if iterations == 0 {
+        return Err(Error::InvalidParameter);
+    }
+
+    let keyed_mac = HmacSha256::new(password);
+
+    for (i, chunk) in output.chunks_mut(SHA256_STATE_LEN * 4).enumerate() {
+        let mut mac = keyed_mac.clone();
+        mac.update(salt);
+        mac.update(&(i as u32 + 1).to_be_bytes());
+        let mut u = mac.finalize();
+        let mut t = u;
+
+        for _ in 1..iterations {
+            let mut mac = keyed_mac.clone();
+            mac.update(&u);
+            u = mac.finalize();
+            for (t, u) in t.iter_mut().zip(u.iter()) {
+                *t ^= u;
+            }
+        }
+
+        chunk.copy_from_slice(&t[..chunk.len()]);
+
+        zero_volatile(&mut u);
+        zero_volatile(&mut t);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pbkdf2<'a>(password: &[u8], salt: &[u8], iterations: u32, output: &'a mut [u8]) -> &'a [u8] {
+        pbkdf2_hmac_sha256(password, salt, iterations, output).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_pbkdf2_hmac_sha256() {
+        let mut out = [0u8; 32];
+        assert_eq!(
+            pbkdf2(b"password", b"salt", 1, &mut out)[..],
+            hex!("120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b")[..]
+        );
+        assert_eq!(
+            pbkdf2(b"password", b"salt", 2, &mut out)[..],
+            hex!("ae4d0c95af6b46d32d0adff928f06dd02a303f8ef3c251dfd6e2d85a95474c43")[..]
+        );
+        assert_eq!(
+            pbkdf2(b"password", b"salt", 4096, &mut out)[..],
+            hex!("c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a")[..]
+        );
+
+        let mut out = [0u8; 40];
+        assert_eq!(
+            pbkdf2(
+                b"passwordPASSWORDpassword",
+                b"saltSALTsaltSALTsaltSALTsaltSALTsalt",
+                4096,
+                &mut out
+            )[..],
+            hex!(
+                "348c89dbcbd32b2f32d814b8116e84cf2b17347ebc1800181c4e2a1fb8dd53e1"
+                "c635518c7dac47e9"
+            )[..]
+        );
+    }
+
+    #[test]
+    fn test_pbkdf2_zero_iterations() {
+        let mut out = [0u8; 32];
+        assert_eq!(
+            pbkdf2_hmac_sha256(b"password", b"salt", 0, &mut out),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(out, [0u8; 32]);
+    }
+
+    // RFC 7914 section 11
+    #[test]
+    fn test_pbkdf2_hmac_sha256_rfc7914() {
+        let mut out = [0u8; 64];
+        assert_eq!(
+            pbkdf2(b"passwd", b"salt", 1, &mut out)[..],
+            hex!(
+                "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc"
+                "49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783"
+            )[..]
+        );
+    }
+}
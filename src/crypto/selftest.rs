@@ -0,0 +1,340 @@
+//! Power-on self tests
+//!
+//! Known answer tests for the ROM backed primitives and a startup health check
+//! of the TRNG, meant to be run once at boot before any keys are used. Nothing
+//! in here panics, failures are reported back to the caller.
+
+use hal::blocking::rng::Read;
+
+use crypto::{Aes128, Aes128Gcm, HmacSha256, Sha224, Sha256};
+
+/// Number of bytes sampled for the TRNG startup test (20000 bits as in FIPS 140-2)
+const TRNG_SAMPLE_BYTES: usize = 2500;
+/// The number of one bits in the sample has to lie strictly between these bounds
+const TRNG_MONOBIT_MIN: u32 = 9725;
+const TRNG_MONOBIT_MAX: u32 = 10275;
+
+/// The individual known answer tests
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Test {
+    Sha256,
+    Sha224,
+    HmacSha256,
+    Aes128,
+    Aes128Gcm,
+}
+
+/// Self test failures
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// A known answer test produced the wrong output
+    KnownAnswer(Test),
+    /// Two consecutive TRNG words were identical
+    TrngStuck,
+    /// The proportion of one bits in the TRNG output is out of bounds
+    TrngBias,
+    /// The TRNG reported an error while being read
+    TrngRead,
+}
+
+/// Outcome of every self test
+#[derive(Debug)]
+pub struct Report {
+    pub sha256: Result<(), Error>,
+    pub sha224: Result<(), Error>,
+    pub hmac_sha256: Result<(), Error>,
+    pub aes128: Result<(), Error>,
+    pub aes128_gcm: Result<(), Error>,
+    pub trng: Result<(), Error>,
+}
+
+impl Report {
+    /// `Ok` if all tests passed, otherwise the first failure
+    pub fn result(&self) -> Result<(), Error> {
+        self.sha256?;
+        self.sha224?;
+        self.hmac_sha256?;
+        self.aes128?;
+        self.aes128_gcm?;
+        self.trng
+    }
+}
+
+/// Run all self tests, every test is run even if an earlier one failed
+pub fn run<R: Read>(rng: &mut R) -> Report {
+    Report {
+        sha256: sha256(),
+        sha224: sha224(),
+        hmac_sha256: hmac_sha256(),
+        aes128: aes128(),
+        aes128_gcm: aes128_gcm(),
+        trng: trng(rng),
+    }
+}
+
+// FIPS 180-4 examples
+const SHA256_ABC: [u8; 32] = [
+    0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae, 0x22, 0x23,
+    0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61, 0xf2, 0x00, 0x15, 0xad,
+];
+const SHA224_ABC: [u8; 28] = [
+    0x23, 0x09, 0x7d, 0x22, 0x34, 0x05, 0xd8, 0x22, 0x86, 0x42, 0xa4, 0x77, 0xbd, 0xa2, 0x55, 0xb3,
+    0x2a, 0xad, 0xbc, 0xe4, 0xbd, 0xa0, 0xb3, 0xf7, 0xe3, 0x6c, 0x9d, 0xa7,
+];
+// RFC 4231 test case 2
+const HMAC_SHA256_JEFE: [u8; 32] = [
+    0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95, 0x75, 0xc7,
+    0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9, 0x64, 0xec, 0x38, 0x43,
+];
+// FIPS-197 appendix C.1
+const AES128_KEY: [u8; 16] = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+];
+const AES128_PLAINTEXT: [u8; 16] = [
+    0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff,
+];
+const AES128_CIPHERTEXT: [u8; 16] = [
+    0x69, 0xc4, 0xe0, 0xd8, 0x6a, 0x7b, 0x04, 0x30, 0xd8, 0xcd, 0xb7, 0x80, 0x70, 0xb4, 0xc5, 0x5a,
+];
+// GCM specification test case 2
+const GCM_CIPHERTEXT: [u8; 16] = [
+    0x03, 0x88, 0xda, 0xce, 0x60, 0xb6, 0xa3, 0x92, 0xf3, 0x28, 0xc2, 0xb9, 0x71, 0xb2, 0xfe, 0x78,
+];
+const GCM_TAG: [u8; 16] = [
+    0xab, 0x6e, 0x47, 0xd4, 0x2c, 0xec, 0x13, 0xbd, 0xf5, 0x3a, 0x67, 0xb2, 0x12, 0x57, 0xbd, 0xdf,
+];
+
+fn check(passed: bool, test: Test) -> Result<(), Error> {
+    if passed {
+        Ok(())
+    } else {
+        Err(Error::KnownAnswer(test))
+    }
+}
+
+/// SHA-256 known answer test through the ROM compression function
+pub fn sha256() -> Result<(), Error> {
+    sha256_kat(b"abc", &SHA256_ABC)
+}
+
+fn sha256_kat(message: &[u8], expected: &[u8; 32]) -> Result<(), Error> {
+    let mut hasher = Sha256::new();
+    hasher.update(message);
+    check(hasher.finalize() == *expected, Test::Sha256)
+}
+
+/// SHA-224 known answer test through the ROM compression function
+pub fn sha224() -> Result<(), Error> {
+    sha224_kat(b"abc", &SHA224_ABC)
+}
+
+fn sha224_kat(message: &[u8], expected: &[u8; 28]) -> Result<(), Error> {
+    let mut hasher = Sha224::new();
+    hasher.update(message);
+    check(hasher.finalize() == *expected, Test::Sha224)
+}
+
+/// HMAC-SHA256 known answer test
+pub fn hmac_sha256() -> Result<(), Error> {
+    hmac_sha256_kat(b"Jefe", b"what do ya want for nothing?", &HMAC_SHA256_JEFE)
+}
+
+fn hmac_sha256_kat(key: &[u8], message: &[u8], expected: &[u8; 32]) -> Result<(), Error> {
+    let mut mac = HmacSha256::new(key);
+    mac.update(message);
+    check(mac.finalize() == *expected, Test::HmacSha256)
+}
+
+/// AES-128 encryption and decryption known answer test through the ROM
+pub fn aes128() -> Result<(), Error> {
+    aes128_kat(&AES128_KEY, &AES128_PLAINTEXT, &AES128_CIPHERTEXT)
+}
+
+fn aes128_kat(key: &[u8; 16], plaintext: &[u8; 16], ciphertext: &[u8; 16]) -> Result<(), Error> {
+    let aes = Aes128::new(key);
+
+    let mut block = *plaintext;
+    aes.encrypt_block(&mut block);
+    check(block == *ciphertext, Test::Aes128)?;
+
+    aes.decrypt_block(&mut block);
+    check(block == *plaintext, Test::Aes128)
+}
+
+/// AES-128-GCM known answer test, covers the ROM GF(2^128) multiplication
+pub fn aes128_gcm() -> Result<(), Error> {
+    aes128_gcm_kat(&[0u8; 16], &GCM_CIPHERTEXT, &GCM_TAG)
+}
+
+fn aes128_gcm_kat(key: &[u8; 16], ciphertext: &[u8; 16], tag: &[u8; 16]) -> Result<(), Error> {
+    let gcm = Aes128Gcm::new(key);
+    let mut buffer = [0u8; 16];
+    let computed_tag = gcm.encrypt_in_place_detached(&[0u8; 12], &[], &mut buffer);
+    check(buffer == *ciphertext && computed_tag == *tag, Test::Aes128Gcm)?;
+
+    check(
+        gcm.decrypt_in_place_detached(&[0u8; 12], &[], &mut buffer, tag).is_ok()
+            && buffer == [0u8; 16],
+        Test::Aes128Gcm,
+    )
+}
+
+/// TRNG startup test
+///
+/// Samples 20000 bits and checks that no two consecutive 32 bit words are
+/// identical and that the number of one bits is within the FIPS 140-2 monobit
+/// bounds.
+pub fn trng<R: Read>(rng: &mut R) -> Result<(), Error> {
+    let mut buffer = [0u8; 32];
+    let mut previous: Option<u32> = None;
+    let mut ones = 0u32;
+
+    let mut remaining = TRNG_SAMPLE_BYTES;
+    while remaining > 0 {
+        let len = core::cmp::min(remaining, buffer.len());
+        rng.read(&mut buffer[..len]).map_err(|_| Error::TrngRead)?;
+
+        for word in buffer[..len].chunks_exact(4) {
+            let word = u32::from_ne_bytes([word[0], word[1], word[2], word[3]]);
+            if previous == Some(word) {
+                return Err(Error::TrngStuck);
+            }
+            previous = Some(word);
+        }
+
+        ones += buffer[..len].iter().map(|b| b.count_ones()).sum::<u32>();
+        remaining -= len;
+    }
+
+    if ones > TRNG_MONOBIT_MIN && ones < TRNG_MONOBIT_MAX {
+        Ok(())
+    } else {
+        Err(Error::TrngBias)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// xorshift32 stand in for a healthy TRNG
+    struct MockTrng {
+        state: u32,
+    }
+
+    impl Read for MockTrng {
+        type Error = ();
+
+        fn read(&mut self, buffer: &mut [u8]) -> Result<(), ()> {
+            for b in buffer.iter_mut() {
+                self.state ^= self.state << 13;
+                self.state ^= self.state >> 17;
+                self.state ^= self.state << 5;
+                *b = self.state as u8;
+            }
+            Ok(())
+        }
+    }
+
+    /// TRNG whose output is stuck at a constant value
+    struct StuckTrng;
+
+    impl Read for StuckTrng {
+        type Error = ();
+
+        fn read(&mut self, buffer: &mut [u8]) -> Result<(), ()> {
+            for b in buffer.iter_mut() {
+                *b = 0x5a;
+            }
+            Ok(())
+        }
+    }
+
+    /// TRNG with a healthy source but one bit stuck high in every byte
+    struct BiasedTrng(MockTrng);
+
+    impl Read for BiasedTrng {
+        type Error = ();
+
+        fn read(&mut self, buffer: &mut [u8]) -> Result<(), ()> {
+            self.0.read(buffer)?;
+            for b in buffer.iter_mut() {
+                *b |= 0x01;
+            }
+            Ok(())
+        }
+    }
+
+    struct FailingTrng;
+
+    impl Read for FailingTrng {
+        type Error = ();
+
+        fn read(&mut self, _buffer: &mut [u8]) -> Result<(), ()> {
+            Err(())
+        }
+    }
+
+    #[test]
+    fn test_selftest_passes() {
+        let report = run(&mut MockTrng { state: 0x1234_5678 });
+        assert_eq!(report.sha256, Ok(()));
+        assert_eq!(report.sha224, Ok(()));
+        assert_eq!(report.hmac_sha256, Ok(()));
+        assert_eq!(report.aes128, Ok(()));
+        assert_eq!(report.aes128_gcm, Ok(()));
+        assert_eq!(report.trng, Ok(()));
+        assert_eq!(report.result(), Ok(()));
+    }
+
+    #[test]
+    fn test_selftest_known_answer_failures() {
+        let mut bad_sha256 = SHA256_ABC;
+        bad_sha256[0] ^= 1;
+        assert_eq!(
+            sha256_kat(b"abc", &bad_sha256),
+            Err(Error::KnownAnswer(Test::Sha256))
+        );
+
+        let mut bad_sha224 = SHA224_ABC;
+        bad_sha224[27] ^= 1;
+        assert_eq!(
+            sha224_kat(b"abc", &bad_sha224),
+            Err(Error::KnownAnswer(Test::Sha224))
+        );
+
+        assert_eq!(
+            hmac_sha256_kat(b"Jeff", b"what do ya want for nothing?", &HMAC_SHA256_JEFE),
+            Err(Error::KnownAnswer(Test::HmacSha256))
+        );
+
+        let mut bad_key = AES128_KEY;
+        bad_key[15] ^= 1;
+        assert_eq!(
+            aes128_kat(&bad_key, &AES128_PLAINTEXT, &AES128_CIPHERTEXT),
+            Err(Error::KnownAnswer(Test::Aes128))
+        );
+
+        let mut bad_tag = GCM_TAG;
+        bad_tag[8] ^= 1;
+        assert_eq!(
+            aes128_gcm_kat(&[0u8; 16], &GCM_CIPHERTEXT, &bad_tag),
+            Err(Error::KnownAnswer(Test::Aes128Gcm))
+        );
+    }
+
+    #[test]
+    fn test_selftest_trng_failures() {
+        assert_eq!(trng(&mut StuckTrng), Err(Error::TrngStuck));
+        assert_eq!(
+            trng(&mut BiasedTrng(MockTrng { state: 0x1234_5678 })),
+            Err(Error::TrngBias)
+        );
+        assert_eq!(trng(&mut FailingTrng), Err(Error::TrngRead));
+
+        let report = run(&mut StuckTrng);
+        assert_eq!(report.sha256, Ok(()));
+        assert_eq!(report.trng, Err(Error::TrngStuck));
+        assert_eq!(report.result(), Err(Error::TrngStuck));
+    }
+}
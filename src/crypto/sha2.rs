@@ -0,0 +1,516 @@
+use core::iter;
+
+use arrayvec::ArrayVec;
+
+#[cfg(feature = "digest")]
+use digest::consts::{U28, U32, U64};
+#[cfg(feature = "digest")]
+use digest::generic_array::GenericArray;
+#[cfg(feature = "digest")]
+use digest::{BlockInput, FixedOutput, Reset, Update};
+
+#[cfg(not(test))]
+use crypto::RomCryptoApi;
+use crypto::{zero_volatile, Wipe};
+use crypto::{SHA256_BLOCKSIZE_BYTES, SHA256_RAM_BUFFER_LEN, SHA256_STATE_LEN};
+
+#[cfg(test)]
+use test_crypto::sha2::sha256_digest_block;
+
+const SHA224_OUTPUT_LEN: usize = 7;
+
+static H256: [u32; SHA256_STATE_LEN] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+static H224: [u32; SHA256_STATE_LEN] = [
+    0xc1059ed8, 0x367cd507, 0x3070dd17, 0xf70e5939, 0xffc00b31, 0x68581511, 0x64f98fa7, 0xbefa4fa4,
+];
+
+/// The SHA-256 message schedule shared by SHA-256 and SHA-224, which only
+/// differ in their initial state and output length.
+///
+/// Message padding and buffering is done in software, each full 512 bit block
+/// is handed to the ROM for compression.
+#[derive(Clone)]
+struct Sha256Engine {
+    state: [u32; SHA256_STATE_LEN],
+    ram_buffer: [u32; SHA256_RAM_BUFFER_LEN],
+    block_buffer: ArrayVec<[u8; SHA256_BLOCKSIZE_BYTES]>,
+    bit_length: u64,
+}
+
+impl Sha256Engine {
+    fn new(iv: &[u32; SHA256_STATE_LEN]) -> Self {
+        Sha256Engine {
+            state: *iv,
+            ram_buffer: [0u32; SHA256_RAM_BUFFER_LEN],
+            block_buffer: ArrayVec::<[u8; SHA256_BLOCKSIZE_BYTES]>::new(),
+            bit_length: 0,
+        }
+    }
+
+    #[cfg(not(test))]
+    fn process_block(&mut self, block: &[u8]) {
+        let rom_api = RomCryptoApi::api_table();
+        //println!("ROM I2C API address is: {:p}", rom_api);
+
+        rom_api.sha256_process_block(&mut self.state, block, &mut self.ram_buffer);
+    }
+
+    #[cfg(test)]
+    fn process_block(&mut self, block: &[u8]) {
+        sha256_digest_block(&mut self.state, block);
+    }
+
+    #[cfg(not(test))]
+    fn process_buffer(&mut self) {
+        let rom_api = RomCryptoApi::api_table();
+        //println!("ROM I2C API address is: {:p}", rom_api);
+
+        rom_api.sha256_process_block(&mut self.state, &self.block_buffer, &mut self.ram_buffer);
+    }
+
+    #[cfg(test)]
+    fn process_buffer(&mut self) {
+        sha256_digest_block(&mut self.state, &self.block_buffer);
+    }
+
+    fn reset(&mut self, iv: &[u32; SHA256_STATE_LEN]) {
+        self.state = *iv;
+        self.block_buffer.clear();
+        self.bit_length = 0;
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.bit_length += (data.len() as u64) * 8;
+        let mut data_in = data;
+
+        if self.block_buffer.len() > 0 {
+            let mut remaining = self.block_buffer.capacity() - self.block_buffer.len();
+            if data_in.len() < remaining {
+                remaining = data_in.len();
+            }
+
+            let (left, right) = data_in.split_at(remaining);
+            self.block_buffer.extend(left.iter().cloned());
+            data_in = right;
+
+            if self.block_buffer.len() == SHA256_BLOCKSIZE_BYTES {
+                self.process_buffer();
+                self.block_buffer.clear();
+            }
+
+            if data_in.is_empty() {
+                return;
+            }
+        }
+
+        let mut iter = data_in.chunks_exact(SHA256_BLOCKSIZE_BYTES);
+
+        for block in iter.by_ref() {
+            self.process_block(block);
+        }
+
+        self.block_buffer.extend(iter.remainder().iter().cloned());
+    }
+
+    /// Pad the message, process the final block(s) and write out as much of
+    /// the state as fits into `out`
+    ///
+    /// All working state is wiped afterwards, `reset` has to be called before
+    /// the engine can be used again.
+    fn finish_into(&mut self, out: &mut [u8]) {
+        self.block_buffer.push(0x80);
+
+        let remaining = self.block_buffer.capacity() - self.block_buffer.len();
+        if remaining < 8 {
+            self.block_buffer.extend(iter::repeat(0u8).take(remaining));
+
+            self.process_buffer();
+
+            self.block_buffer.clear();
+
+            self.block_buffer
+                .extend(iter::repeat(0u8).take(SHA256_BLOCKSIZE_BYTES - 8));
+        } else {
+            self.block_buffer
+                .extend(iter::repeat(0u8).take(remaining - 8));
+        }
+        self.block_buffer
+            .extend(self.bit_length.to_be_bytes().iter().cloned());
+
+        self.process_buffer();
+
+        for (chunk, word) in out.chunks_exact_mut(4).zip(self.state.iter()) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+
+        self.wipe();
+    }
+}
+
+impl Wipe for Sha256Engine {
+    fn wipe(&mut self) {
+        zero_volatile(&mut self.state);
+        // The ROM uses this as scratch space for the message schedule
+        zero_volatile(&mut self.ram_buffer);
+
+        // Fill up the block buffer so the unused tail is wiped as well
+        let len = self.block_buffer.len();
+        self.block_buffer
+            .extend(iter::repeat(0u8).take(SHA256_BLOCKSIZE_BYTES - len));
+        zero_volatile(&mut self.block_buffer);
+        self.block_buffer.clear();
+
+        self.bit_length = 0;
+    }
+}
+
+/// SHA-256 hasher backed by the CRYA `crya_sha_process` ROM function.
+#[derive(Clone)]
+pub struct Sha256 {
+    engine: Sha256Engine,
+}
+
+impl Sha256 {
+    pub fn new() -> Self {
+        Sha256 {
+            engine: Sha256Engine::new(&H256),
+        }
+    }
+
+    /// Reset the hasher to its initial state, discarding any buffered input
+    pub fn reset(&mut self) {
+        self.engine.reset(&H256);
+    }
+
+    /// Feed `data` into the hasher, may be called any number of times
+    pub fn update(&mut self, data: &[u8]) {
+        self.engine.update(data);
+    }
+
+    /// Pad the message, process the final block(s) and return the digest
+    pub fn finalize(mut self) -> [u8; SHA256_STATE_LEN * 4] {
+        let mut ret = [0u8; SHA256_STATE_LEN * 4];
+        self.engine.finish_into(&mut ret);
+        ret
+    }
+
+    /// Return the digest and reset the hasher for a new message
+    pub fn finalize_reset(&mut self) -> [u8; SHA256_STATE_LEN * 4] {
+        let mut ret = [0u8; SHA256_STATE_LEN * 4];
+        self.engine.finish_into(&mut ret);
+        self.reset();
+        ret
+    }
+}
+
+/// Wiping leaves the hasher in its initial state
+impl Wipe for Sha256 {
+    fn wipe(&mut self) {
+        self.engine.wipe();
+        self.engine.reset(&H256);
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Sha256::new()
+    }
+}
+
+/// SHA-224 hasher, using the same ROM compression function as `Sha256`.
+#[derive(Clone)]
+pub struct Sha224 {
+    engine: Sha256Engine,
+}
+
+impl Sha224 {
+    pub fn new() -> Self {
+        Sha224 {
+            engine: Sha256Engine::new(&H224),
+        }
+    }
+
+    /// Reset the hasher to its initial state, discarding any buffered input
+    pub fn reset(&mut self) {
+        self.engine.reset(&H224);
+    }
+
+    /// Feed `data` into the hasher, may be called any number of times
+    pub fn update(&mut self, data: &[u8]) {
+        self.engine.update(data);
+    }
+
+    /// Pad the message, process the final block(s) and return the digest
+    pub fn finalize(mut self) -> [u8; SHA224_OUTPUT_LEN * 4] {
+        let mut ret = [0u8; SHA224_OUTPUT_LEN * 4];
+        self.engine.finish_into(&mut ret);
+        ret
+    }
+
+    /// Return the digest and reset the hasher for a new message
+    pub fn finalize_reset(&mut self) -> [u8; SHA224_OUTPUT_LEN * 4] {
+        let mut ret = [0u8; SHA224_OUTPUT_LEN * 4];
+        self.engine.finish_into(&mut ret);
+        self.reset();
+        ret
+    }
+}
+
+/// Wiping leaves the hasher in its initial state
+impl Wipe for Sha224 {
+    fn wipe(&mut self) {
+        self.engine.wipe();
+        self.engine.reset(&H224);
+    }
+}
+
+impl Default for Sha224 {
+    fn default() -> Self {
+        Sha224::new()
+    }
+}
+
+#[cfg(feature = "digest")]
+macro_rules! digest_impls {
+    ($Type:ident, $OutputSize:ident) => {
+        impl BlockInput for $Type {
+            type BlockSize = U64;
+        }
+
+        impl Update for $Type {
+            fn update(&mut self, data: impl AsRef<[u8]>) {
+                $Type::update(self, data.as_ref());
+            }
+        }
+
+        impl FixedOutput for $Type {
+            type OutputSize = $OutputSize;
+
+            fn finalize_into(mut self, out: &mut GenericArray<u8, Self::OutputSize>) {
+                self.finalize_into_reset(out);
+            }
+
+            fn finalize_into_reset(&mut self, out: &mut GenericArray<u8, Self::OutputSize>) {
+                self.engine.finish_into(out);
+                $Type::reset(self);
+            }
+        }
+
+        impl Reset for $Type {
+            fn reset(&mut self) {
+                $Type::reset(self);
+            }
+        }
+    };
+}
+
+#[cfg(feature = "digest")]
+digest_impls!(Sha256, U32);
+#[cfg(feature = "digest")]
+digest_impls!(Sha224, U28);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //#[test]
+    //fn works() {
+        //let rom_api = RomCryptoApi::api_table();
+        //println!("{:p}", rom_api.crya_sha_process);
+    //}
+
+    #[test]
+    fn test_sha256() {
+        let mut hasher = Sha256::new();
+        hasher.update(b"hello world");
+        let result = hasher.finalize();
+        assert_eq!(
+            result[..],
+            hex!("b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9")[..]
+        );
+
+        let mut hasher = Sha256::new();
+        hasher.update(b"hello");
+        hasher.update(b" world");
+        let result = hasher.finalize();
+        assert_eq!(
+            result[..],
+            hex!("b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9")[..]
+        );
+
+        let mut hasher = Sha256::new();
+        hasher.update(b"");
+        let result = hasher.finalize();
+        assert_eq!(
+            result[..],
+            hex!("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")[..]
+        );
+
+        let mut hasher = Sha256::new();
+        hasher.update(b"Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat. Duis aute irure dolor in reprehenderit in voluptate velit esse cillum dolore eu fugiat nulla pariatur. Excepteur sint occaecat cupidatat non proident, sunt in culpa qui officia deserunt mollit anim id est laborum.");
+        let result = hasher.finalize();
+        assert_eq!(
+            result[..],
+            hex!("2d8c2f6d978ca21712b5f6de36c9d31fa8e96a4fa5d8ff8b0188dfb9e7c171bb")[..]
+        );
+
+        let mut hasher = Sha256::new();
+        hasher.update(b"Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat. Duis aute irure dolor in reprehenderit in voluptate velit esse cillum dolore eu fugiat nulla pariatur. Excepteur sint occaecat cupidatat non proident, sunt in culpa qui officia deserunt mollit anim id est");
+        hasher.update(b" laborum.");
+        let result = hasher.finalize();
+        assert_eq!(
+            result[..],
+            hex!("2d8c2f6d978ca21712b5f6de36c9d31fa8e96a4fa5d8ff8b0188dfb9e7c171bb")[..]
+        );
+    }
+
+    #[test]
+    fn test_sha256_block_boundary() {
+        let mut hasher = Sha256::new();
+        hasher.update(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq");
+        let result = hasher.finalize();
+        assert_eq!(
+            result[..],
+            hex!("248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1")[..]
+        );
+
+        let mut hasher = Sha256::new();
+        for _ in 0..1000 {
+            hasher.update(&[b'a'; 1000]);
+        }
+        let result = hasher.finalize();
+        assert_eq!(
+            result[..],
+            hex!("cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0")[..]
+        );
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn test_digest_traits() {
+        use digest::Digest;
+
+        fn hash<D: Digest>(chunks: &[&[u8]]) -> GenericArray<u8, D::OutputSize> {
+            let mut hasher = D::new();
+            for chunk in chunks {
+                Digest::update(&mut hasher, chunk);
+            }
+            hasher.finalize()
+        }
+
+        assert_eq!(
+            hash::<Sha256>(&[b"hello", b" world"])[..],
+            hex!("b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9")[..]
+        );
+        assert_eq!(
+            hash::<Sha256>(&[])[..],
+            hex!("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")[..]
+        );
+        assert_eq!(
+            <Sha256 as Digest>::digest(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")[..],
+            hex!("248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1")[..]
+        );
+
+        // finalize_reset must leave the hasher ready for a fresh message
+        let mut hasher = <Sha256 as Digest>::new();
+        Digest::update(&mut hasher, b"hello world");
+        let first = hasher.finalize_reset();
+        Digest::update(&mut hasher, b"");
+        let second = hasher.finalize_reset();
+        assert_eq!(
+            first[..],
+            hex!("b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9")[..]
+        );
+        assert_eq!(
+            second[..],
+            hex!("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")[..]
+        );
+    }
+
+    #[test]
+    fn test_sha256_reset() {
+        let mut hasher = Sha256::new();
+        hasher.update(b"some data that should be discarded");
+        hasher.reset();
+        hasher.update(b"hello world");
+        let result = hasher.finalize();
+        assert_eq!(
+            result[..],
+            hex!("b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9")[..]
+        );
+    }
+
+    #[test]
+    fn test_sha256_wipe() {
+        let mut hasher = Sha256::new();
+        hasher.update(b"secret key material that is not block aligned");
+        // Stand in for the message schedule the ROM leaves behind
+        hasher.engine.ram_buffer = [0xa5a5_a5a5; SHA256_RAM_BUFFER_LEN];
+
+        let result = hasher.finalize_reset();
+        assert_eq!(hasher.engine.ram_buffer, [0u32; SHA256_RAM_BUFFER_LEN]);
+        assert_eq!(hasher.engine.state, H256);
+        assert!(hasher.engine.block_buffer.is_empty());
+        assert_eq!(hasher.engine.bit_length, 0);
+
+        hasher.update(b"secret key material that is not block aligned");
+        assert_eq!(hasher.finalize(), result);
+
+        let mut hasher = Sha256::new();
+        hasher.update(b"hello");
+        hasher.wipe();
+        hasher.update(b"hello world");
+        assert_eq!(
+            hasher.finalize()[..],
+            hex!("b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9")[..]
+        );
+    }
+
+    #[test]
+    fn test_sha224() {
+        let mut hasher = Sha224::new();
+        hasher.update(b"abc");
+        assert_eq!(
+            hasher.finalize()[..],
+            hex!("23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7")[..]
+        );
+
+        let mut hasher = Sha224::new();
+        hasher.update(b"");
+        assert_eq!(
+            hasher.finalize()[..],
+            hex!("d14a028c2a3a2bc9476102bb288234c415a2b01f828ea62ac5b3e42f")[..]
+        );
+
+        let mut hasher = Sha224::new();
+        hasher.update(b"abcdbcdecdefdefgefghfghighijhijk");
+        hasher.update(b"ijkljklmklmnlmnomnopnopq");
+        assert_eq!(
+            hasher.finalize()[..],
+            hex!("75388b16512776cc5dba5da1fd890150b0c6455cb4f58b1952522525")[..]
+        );
+
+        let mut hasher = Sha224::new();
+        hasher.update(b"hello world");
+        hasher.reset();
+        hasher.update(b"abc");
+        assert_eq!(
+            hasher.finalize()[..],
+            hex!("23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7")[..]
+        );
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn test_sha224_digest_traits() {
+        use digest::Digest;
+
+        assert_eq!(
+            <Sha224 as Digest>::digest(b"abc")[..],
+            hex!("23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7")[..]
+        );
+    }
+}
@@ -0,0 +1,428 @@
+#[cfg(feature = "rand_core")]
+use rand_core::{CryptoRng, RngCore};
+
+use crypto::hkdf;
+use crypto::{zero_volatile, Error, Wipe};
+
+/// Length of X25519 secret keys, public keys and shared secrets in bytes
+pub const X25519_KEY_LEN: usize = 32;
+
+const BASEPOINT: [u8; X25519_KEY_LEN] = [
+    9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+/// An X25519 (RFC 7748) key pair for ephemeral Diffie-Hellman
+///
+/// The secret scalar is wiped when the key pair is dropped.
+pub struct X25519KeyPair {
+    secret: [u8; X25519_KEY_LEN],
+    public: [u8; X25519_KEY_LEN],
+}
+
+impl X25519KeyPair {
+    /// Generate a new key pair with a secret drawn from `rng`, usually `rng::Rng`
+    #[cfg(feature = "rand_core")]
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let mut secret = [0u8; X25519_KEY_LEN];
+        rng.fill_bytes(&mut secret);
+        let ret = Self::from_secret_bytes(&secret);
+        zero_volatile(&mut secret);
+        ret
+    }
+
+    /// Create a key pair from a 32 byte secret, which is clamped as per RFC 7748
+    pub fn from_secret_bytes(secret: &[u8; X25519_KEY_LEN]) -> Self {
+        let mut public = [0u8; X25519_KEY_LEN];
+        scalarmult(&mut public, secret, &BASEPOINT);
+
+        X25519KeyPair {
+            secret: *secret,
+            public,
+        }
+    }
+
+    /// The public key to send to the peer
+    pub fn public_key(&self) -> [u8; X25519_KEY_LEN] {
+        self.public
+    }
+
+    /// Compute the shared secret with the peer's public key
+    ///
+    /// Fails with `Error::InvalidEncoding` for low order peer keys, which
+    /// would make the shared secret all zeros.
+    pub fn diffie_hellman(
+        &self,
+        peer_public: &[u8; X25519_KEY_LEN],
+    ) -> Result<X25519SharedSecret, Error> {
+        let mut shared = X25519SharedSecret {
+            bytes: [0u8; X25519_KEY_LEN],
+        };
+        scalarmult(&mut shared.bytes, &self.secret, peer_public);
+
+        let acc = shared.bytes.iter().fold(0u8, |acc, b| acc | b);
+        if acc == 0 {
+            return Err(Error::InvalidEncoding);
+        }
+
+        Ok(shared)
+    }
+}
+
+impl Wipe for X25519KeyPair {
+    fn wipe(&mut self) {
+        zero_volatile(&mut self.secret);
+        zero_volatile(&mut self.public);
+    }
+}
+
+/// The raw output of an X25519 exchange, wiped when dropped
+///
+/// It is not uniformly random and should go through `derive_key` before use.
+pub struct X25519SharedSecret {
+    bytes: [u8; X25519_KEY_LEN],
+}
+
+impl X25519SharedSecret {
+    pub fn as_bytes(&self) -> &[u8; X25519_KEY_LEN] {
+        &self.bytes
+    }
+
+    /// Derive key material into `okm` with HKDF-SHA256, see `hkdf::derive`
+    pub fn derive_key(&self, salt: &[u8], info: &[u8], okm: &mut [u8]) -> Result<(), Error> {
+        hkdf::derive(salt, &self.bytes, info, okm)
+    }
+}
+
+impl Wipe for X25519SharedSecret {
+    fn wipe(&mut self) {
+        zero_volatile(&mut self.bytes);
+    }
+}
+
+/// Field element mod 2^255 - 19 as sixteen signed 16 bit limbs
+///
+/// All operations run in constant time, the representation follows TweetNaCl.
+type Fe = [i64; 16];
+
+const FE_121665: Fe = [0xdb41, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+fn fe_carry(o: &mut Fe) {
+    for i in 0..16 {
+        o[i] += 1 << 16;
+        let c = o[i] >> 16;
+        if i < 15 {
+            o[i + 1] += c - 1;
+        } else {
+            o[0] += 38 * (c - 1);
+        }
+        o[i] -= c << 16;
+    }
+}
+
+/// Swap `p` and `q` if `b` is 1, without branching on `b`
+fn fe_cswap(p: &mut Fe, q: &mut Fe, b: i64) {
+    let mask = !(b - 1);
+    for (p, q) in p.iter_mut().zip(q.iter_mut()) {
+        let t = mask & (*p ^ *q);
+        *p ^= t;
+        *q ^= t;
+    }
+}
+
+fn fe_pack(o: &mut [u8; X25519_KEY_LEN], n: &Fe) {
+    let mut t = *n;
+    let mut m: Fe = [0; 16];
+    fe_carry(&mut t);
+    fe_carry(&mut t);
+    fe_carry(&mut t);
+
+    for _ in 0..2 {
+        m[0] = t[0] - 0xffed;
+        for i in 1..15 {
+            m[i] = t[i] - 0xffff - ((m[i - 1] >> 16) & 1);
+            m[i - 1] &= 0xffff;
+        }
+        m[15] = t[15] - 0x7fff - ((m[14] >> 16) & 1);
+        let b = (m[15] >> 16) & 1;
+        m[14] &= 0xffff;
+        fe_cswap(&mut t, &mut m, 1 - b);
+    }
+
+    for (o, t) in o.chunks_exact_mut(2).zip(t.iter()) {
+        o[0] = *t as u8;
+        o[1] = (*t >> 8) as u8;
+    }
+
+    zero_volatile(&mut t);
+    zero_volatile(&mut m);
+}
+
+fn fe_unpack(n: &[u8; X25519_KEY_LEN]) -> Fe {
+    let mut o: Fe = [0; 16];
+    for (o, n) in o.iter_mut().zip(n.chunks_exact(2)) {
+        *o = n[0] as i64 + ((n[1] as i64) << 8);
+    }
+    o[15] &= 0x7fff;
+    o
+}
+
+fn fe_add(a: &Fe, b: &Fe) -> Fe {
+    let mut o: Fe = [0; 16];
+    for i in 0..16 {
+        o[i] = a[i] + b[i];
+    }
+    o
+}
+
+fn fe_sub(a: &Fe, b: &Fe) -> Fe {
+    let mut o: Fe = [0; 16];
+    for i in 0..16 {
+        o[i] = a[i] - b[i];
+    }
+    o
+}
+
+fn fe_mul(a: &Fe, b: &Fe) -> Fe {
+    let mut t = [0i64; 31];
+    for i in 0..16 {
+        for j in 0..16 {
+            t[i + j] += a[i] * b[j];
+        }
+    }
+    for i in 0..15 {
+        t[i] += 38 * t[i + 16];
+    }
+
+    let mut o: Fe = [0; 16];
+    o.copy_from_slice(&t[..16]);
+    fe_carry(&mut o);
+    fe_carry(&mut o);
+    zero_volatile(&mut t);
+    o
+}
+
+fn fe_square(a: &Fe) -> Fe {
+    fe_mul(a, a)
+}
+
+/// a^(p - 2)
+fn fe_invert(a: &Fe) -> Fe {
+    let mut c = *a;
+    for i in (0..254).rev() {
+        c = fe_square(&c);
+        if i != 2 && i != 4 {
+            c = fe_mul(&c, a);
+        }
+    }
+    c
+}
+
+/// Montgomery ladder computing `q = n * p` on the u coordinate
+fn scalarmult(q: &mut [u8; X25519_KEY_LEN], n: &[u8; X25519_KEY_LEN], p: &[u8; X25519_KEY_LEN]) {
+    let mut z = *n;
+    z[31] = (z[31] & 127) | 64;
+    z[0] &= 248;
+
+    let x = fe_unpack(p);
+    let mut a: Fe = [0; 16];
+    let mut b = x;
+    let mut c: Fe = [0; 16];
+    let mut d: Fe = [0; 16];
+    a[0] = 1;
+    d[0] = 1;
+
+    for i in (0..255).rev() {
+        let r = ((z[i >> 3] >> (i & 7)) & 1) as i64;
+        fe_cswap(&mut a, &mut b, r);
+        fe_cswap(&mut c, &mut d, r);
+
+        let mut e = fe_add(&a, &c);
+        a = fe_sub(&a, &c);
+        c = fe_add(&b, &d);
+        b = fe_sub(&b, &d);
+        d = fe_square(&e);
+        let f = fe_square(&a);
+        a = fe_mul(&c, &a);
+        c = fe_mul(&b, &e);
+        e = fe_add(&a, &c);
+        a = fe_sub(&a, &c);
+        b = fe_square(&a);
+        c = fe_sub(&d, &f);
+        a = fe_mul(&c, &FE_121665);
+        a = fe_add(&a, &d);
+        c = fe_mul(&c, &a);
+        a = fe_mul(&d, &f);
+        d = fe_mul(&b, &x);
+        b = fe_square(&e);
+
+        fe_cswap(&mut a, &mut b, r);
+        fe_cswap(&mut c, &mut d, r);
+    }
+
+    let c = fe_invert(&c);
+    fe_pack(q, &fe_mul(&a, &c));
+
+    zero_volatile(&mut z);
+    zero_volatile(&mut a);
+    zero_volatile(&mut b);
+    zero_volatile(&mut d);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 7748 section 6.1
+    const ALICE_SECRET: [u8; 32] =
+        hex!("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+    const ALICE_PUBLIC: [u8; 32] =
+        hex!("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a");
+    const BOB_SECRET: [u8; 32] =
+        hex!("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+    const BOB_PUBLIC: [u8; 32] =
+        hex!("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f");
+    const SHARED: [u8; 32] =
+        hex!("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742");
+
+    #[test]
+    fn test_x25519_rfc7748_scalarmult() {
+        let mut out = [0u8; 32];
+        scalarmult(
+            &mut out,
+            &hex!("a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4"),
+            &hex!("e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c"),
+        );
+        assert_eq!(
+            out,
+            hex!("c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552")
+        );
+
+        // The most significant bit of the u coordinate is ignored
+        scalarmult(
+            &mut out,
+            &hex!("4b66e9d4d1b4673c5ad22691957d6af5c11b6421e0ea01d42ca4169e7918ba0d"),
+            &hex!("e5210f12786811d3f4b7959d0538ae2c31dbe7106fc03c3efc4cd549c715a493"),
+        );
+        assert_eq!(
+            out,
+            hex!("95cbde9476e8907d7aade45cb4b873f88b595a68799fa152e6f8f7647aac7957")
+        );
+    }
+
+    #[test]
+    fn test_x25519_rfc7748_iterated() {
+        let mut k = BASEPOINT;
+        let mut u = BASEPOINT;
+        for i in 0..1000 {
+            let mut out = [0u8; 32];
+            scalarmult(&mut out, &k, &u);
+            u = k;
+            k = out;
+
+            if i == 0 {
+                assert_eq!(
+                    k,
+                    hex!("422c8e7a6227d7bca1350b3e2bb7279f7897b87bb6854b783c60e80311ae3079")
+                );
+            }
+        }
+        assert_eq!(
+            k,
+            hex!("684cf59ba83309552800ef566f2f4d3c1c3887c49360e3875f2eb94d99532c51")
+        );
+    }
+
+    #[test]
+    fn test_x25519_key_agreement() {
+        let alice = X25519KeyPair::from_secret_bytes(&ALICE_SECRET);
+        let bob = X25519KeyPair::from_secret_bytes(&BOB_SECRET);
+        assert_eq!(alice.public_key(), ALICE_PUBLIC);
+        assert_eq!(bob.public_key(), BOB_PUBLIC);
+
+        let alice_shared = alice.diffie_hellman(&BOB_PUBLIC).unwrap();
+        let bob_shared = bob.diffie_hellman(&ALICE_PUBLIC).unwrap();
+        assert_eq!(*alice_shared.as_bytes(), SHARED);
+        assert_eq!(*bob_shared.as_bytes(), SHARED);
+
+        let mut alice_key = [0u8; 16];
+        let mut bob_key = [0u8; 16];
+        alice_shared
+            .derive_key(b"salt", b"session", &mut alice_key)
+            .unwrap();
+        bob_shared
+            .derive_key(b"salt", b"session", &mut bob_key)
+            .unwrap();
+        assert_eq!(alice_key, bob_key);
+
+        let mut expected = [0u8; 16];
+        hkdf::derive(b"salt", &SHARED, b"session", &mut expected).unwrap();
+        assert_eq!(alice_key, expected);
+    }
+
+    #[test]
+    fn test_x25519_low_order_point() {
+        let alice = X25519KeyPair::from_secret_bytes(&ALICE_SECRET);
+        assert!(alice.diffie_hellman(&[0u8; 32]).is_err());
+
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        assert!(alice.diffie_hellman(&one).is_err());
+    }
+
+    #[test]
+    fn test_x25519_wipe() {
+        let mut alice = X25519KeyPair::from_secret_bytes(&ALICE_SECRET);
+        alice.wipe();
+        assert_eq!(alice.secret, [0u8; 32]);
+    }
+
+    #[cfg(feature = "rand_core")]
+    #[test]
+    fn test_x25519_generate() {
+        use rand_core::{impls, Error as RandError};
+
+        struct CountingRng(u8);
+
+        impl RngCore for CountingRng {
+            fn next_u32(&mut self) -> u32 {
+                impls::next_u32_via_fill(self)
+            }
+
+            fn next_u64(&mut self) -> u64 {
+                impls::next_u64_via_fill(self)
+            }
+
+            fn fill_bytes(&mut self, dest: &mut [u8]) {
+                for b in dest.iter_mut() {
+                    *b = self.0;
+                    self.0 = self.0.wrapping_add(1);
+                }
+            }
+
+            fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), RandError> {
+                self.fill_bytes(dest);
+                Ok(())
+            }
+        }
+
+        impl CryptoRng for CountingRng {}
+
+        let mut rng = CountingRng(0);
+        let alice = X25519KeyPair::generate(&mut rng);
+        let bob = X25519KeyPair::generate(&mut rng);
+
+        let mut secret = [0u8; 32];
+        for (i, b) in secret.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        assert_eq!(
+            alice.public_key(),
+            X25519KeyPair::from_secret_bytes(&secret).public_key()
+        );
+
+        assert_eq!(
+            alice.diffie_hellman(&bob.public_key()).unwrap().as_bytes(),
+            bob.diffie_hellman(&alice.public_key()).unwrap().as_bytes()
+        );
+    }
+}
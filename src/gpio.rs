@@ -1,9 +1,15 @@
 use core::marker::PhantomData;
 
 use atsaml11xxx::port::group::{DIRCLR, DIRSET, OUTCLR, OUTSET, PINCFG, PMUX};
-use atsaml11xxx::PORT_SEC;
 use hal::digital::OutputPin;
 
+/// The PORT alias driven by the HAL, the secure `PORT_SEC` alias by default
+/// and the non-secure `PORT` alias with the `nonsecure` feature
+#[cfg(not(feature = "nonsecure"))]
+pub use atsaml11xxx::PORT_SEC as PORT;
+#[cfg(feature = "nonsecure")]
+pub use atsaml11xxx::PORT;
+
 /// Extension trait to split a GPIO peripheral in independent pins and registers
 pub trait GpioExt {
     /// The to split the GPIO into
@@ -13,6 +19,11 @@ pub trait GpioExt {
     fn split(self) -> Self::Parts;
 }
 
+/// Number of a pin within its PORT group
+pub trait PinId {
+    const PIN_NO: u8;
+}
+
 /// Input mode (type state)
 pub struct Input<MODE> {
     _mode: PhantomData<MODE>,
@@ -111,6 +122,10 @@ macro_rules! pin {
             _mode: PhantomData<MODE>,
         }
 
+        impl<MODE> PinId for $PinType<MODE> {
+            const PIN_NO: u8 = $pin_no;
+        }
+
         function!(PfA, into_function_a, 0);
         function!(PfB, into_function_b, 1);
         function!(PfC, into_function_c, 2);
@@ -251,7 +266,7 @@ macro_rules! pin {
 
             fn toggle_impl(&mut self) {
                 unsafe {
-                    (*PORT_SEC::ptr()).$group.$outtgl.write(|bits| {
+                    (*PORT::ptr()).$group.$outtgl.write(|bits| {
                         bits.bits(1 << $pin_no);
                         bits
                     });
@@ -269,29 +284,29 @@ macro_rules! pin {
         #[cfg(feature = "unproven")]
         impl<MODE> InputPin for $PinType<Input<MODE>> {
             fn is_high(&self) -> bool {
-                unsafe { (((*PORT_SEC::ptr()).$group.$in.read().bits()) & (1 << $pin_no)) != 0 }
+                unsafe { (((*PORT::ptr()).$group.$in.read().bits()) & (1 << $pin_no)) != 0 }
             }
 
             fn is_low(&self) -> bool {
-                unsafe { (((*PORT_SEC::ptr()).$group.$in.read().bits()) & (1 << $pin_no)) == 0 }
+                unsafe { (((*PORT::ptr()).$group.$in.read().bits()) & (1 << $pin_no)) == 0 }
             }
         }
 
         #[cfg(feature = "unproven")]
         impl<MODE> StatefulOutputPin for $PinType<Output<MODE>> {
             fn is_set_high(&self) -> bool {
-                unsafe { (((*PORT_SEC::ptr()).$group.$out.read().bits()) & (1 << $pin_no)) != 0 }
+                unsafe { (((*PORT::ptr()).$group.$out.read().bits()) & (1 << $pin_no)) != 0 }
             }
 
             fn is_set_low(&self) -> bool {
-                unsafe { (((*PORT_SEC::ptr()).$group.$out.read().bits()) & (1 << $pin_no)) == 0 }
+                unsafe { (((*PORT::ptr()).$group.$out.read().bits()) & (1 << $pin_no)) == 0 }
             }
         }
 
         impl<MODE> OutputPin for $PinType<Output<MODE>> {
             fn set_high(&mut self) {
                 unsafe {
-                    (*PORT_SEC::ptr()).$group.$outset.write(|bits| {
+                    (*PORT::ptr()).$group.$outset.write(|bits| {
                         bits.bits(1 << $pin_no);
                         bits
                     });
@@ -300,7 +315,7 @@ macro_rules! pin {
 
             fn set_low(&mut self) {
                 unsafe {
-                    (*PORT_SEC::ptr()).$group.$outclr.write(|bits| {
+                    (*PORT::ptr()).$group.$outclr.write(|bits| {
                         bits.bits(1 << $pin_no);
                         bits
                     });
@@ -317,22 +332,22 @@ pub struct Port {
 
 impl Port {
     fn dirset(&mut self) -> &DIRSET {
-        unsafe { &(*PORT_SEC::ptr()).group0.dirset }
+        unsafe { &(*PORT::ptr()).group0.dirset }
     }
     fn dirclr(&mut self) -> &DIRCLR {
-        unsafe { &(*PORT_SEC::ptr()).group0.dirclr }
+        unsafe { &(*PORT::ptr()).group0.dirclr }
     }
     fn pincfg(&mut self) -> &[PINCFG; 32] {
-        unsafe { &(*PORT_SEC::ptr()).group0.pincfg }
+        unsafe { &(*PORT::ptr()).group0.pincfg }
     }
     fn outset(&mut self) -> &OUTSET {
-        unsafe { &(*PORT_SEC::ptr()).group0.outset }
+        unsafe { &(*PORT::ptr()).group0.outset }
     }
     fn outclr(&mut self) -> &OUTCLR {
-        unsafe { &(*PORT_SEC::ptr()).group0.outclr }
+        unsafe { &(*PORT::ptr()).group0.outclr }
     }
     fn pmux(&mut self) -> &[PMUX; 16] {
-        unsafe { &(*PORT_SEC::ptr()).group0.pmux }
+        unsafe { &(*PORT::ptr()).group0.pmux }
     }
 }
 
@@ -352,10 +367,10 @@ pub struct Parts {
     )+
 }
 
-impl GpioExt for PORT_SEC {
+impl GpioExt for PORT {
     type Parts = Parts;
 
-    /// Split the PORT_SEC peripheral into discrete pins
+    /// Split the PORT peripheral into discrete pins
     fn split(self) -> Parts {
         Parts {
             port: Port {_0: ()},
@@ -432,7 +447,7 @@ $crate::paste::item! {
 impl $Type {
     /// Returns the pins for the device
     $crate::paste::item! {
-        pub fn new(port: $target_device::PORT_SEC) -> Self {
+        pub fn new(port: $crate::gpio::PORT) -> Self {
             let pins = port.split();
             $Type {
                 port: pins.port,
@@ -0,0 +1,416 @@
+//! IDAU partitioning of flash, data flash and RAM
+//!
+//! The boot ROM configures the IDAU at reset from the user row: AS secure
+//! and ANSC non-secure callable application flash, DS secure data flash and
+//! RS secure RAM. They follow the BS secure boot flash, including its BNSC
+//! non-secure callable part, from the boot configuration row. Everything
+//! past the secure regions is non-secure.
+//!
+//! ```text
+//! flash: | BS (BNSC at the end) | AS (ANSC at the end) | non-secure |
+//! RAM:   | RS                   | non-secure                        |
+//! ```
+
+use core::fmt;
+
+use nvm::{self, BootConfigRow, Nvm, UserRow};
+
+/// Flash size, as in `memory.x`
+pub const FLASH_SIZE: u32 = 64 * 1024;
+/// RAM size, as in `memory.x`
+pub const RAM_SIZE: u32 = 16 * 1024;
+/// Data flash size
+pub const DATA_FLASH_SIZE: u32 = 2 * 1024;
+
+/// Start of flash
+pub const FLASH_ORIGIN: u32 = 0x0000_0000;
+/// Start of RAM
+pub const RAM_ORIGIN: u32 = 0x2000_0000;
+
+/// Granularity of BS, AS and DS
+const FLASH_GRANULE: u32 = 0x100;
+/// Granularity of BNSC and ANSC
+const NSC_GRANULE: u32 = 0x20;
+/// Granularity of RS
+const RAM_GRANULE: u32 = 0x80;
+
+/// Field widths of BS, BNSC, AS, ANSC, DS and RS
+const BS_WIDTH: u32 = 8;
+const BNSC_WIDTH: u32 = 6;
+const AS_WIDTH: u32 = 8;
+const ANSC_WIDTH: u32 = 6;
+const DS_WIDTH: u32 = 4;
+const RS_WIDTH: u32 = 7;
+
+/// IDAU configuration errors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// A region size is not a multiple of its granularity or too large for
+    /// its field
+    InvalidSize,
+    /// The non-secure callable part is larger than its secure region
+    NscTooLarge,
+    /// The secure flash, data flash or RAM exceeds the memory size
+    DoesNotFit,
+    /// The boot region differs from the one in the boot configuration row
+    BootMismatch,
+    /// Programming the user row failed
+    Nvm(nvm::Error),
+}
+
+impl From<nvm::Error> for Error {
+    fn from(err: nvm::Error) -> Self {
+        Error::Nvm(err)
+    }
+}
+
+/// Encode a region size as a field value, granules are powers of two
+fn units(size: u32, granule: u32, width: u32) -> Result<u8, Error> {
+    if size & (granule - 1) != 0 || size / granule >= 1 << width {
+        return Err(Error::InvalidSize);
+    }
+    Ok((size / granule) as u8)
+}
+
+fn round_up(size: u32, granule: u32) -> Result<u32, Error> {
+    match size.checked_add(granule - 1) {
+        Some(size) => Ok(size & !(granule - 1)),
+        None => Err(Error::InvalidSize),
+    }
+}
+
+/// A memory region, as in a linker script `MEMORY` block
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub origin: u32,
+    pub length: u32,
+}
+
+/// Sizes in bytes of the IDAU regions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    /// Secure boot flash (BS), only changed through the boot configuration row
+    pub boot_secure: u32,
+    /// Non-secure callable part of the boot flash (BNSC)
+    pub boot_nsc: u32,
+    /// Secure application flash (AS)
+    pub app_secure: u32,
+    /// Non-secure callable part of the application flash (ANSC)
+    pub app_nsc: u32,
+    /// Secure data flash (DS)
+    pub data_secure: u32,
+    /// Secure RAM (RS)
+    pub ram_secure: u32,
+}
+
+impl Layout {
+    /// Read the current layout from the user row and boot configuration row
+    pub fn read() -> Self {
+        Layout::from_rows(&UserRow::read(), &BootConfigRow::read())
+    }
+
+    /// Layout configured by the given rows
+    pub fn from_rows(urow: &UserRow, bocor: &BootConfigRow) -> Self {
+        Layout {
+            boot_secure: bocor.boot_secure() as u32 * FLASH_GRANULE,
+            boot_nsc: bocor.boot_nsc() as u32 * NSC_GRANULE,
+            app_secure: urow.app_secure() as u32 * FLASH_GRANULE,
+            app_nsc: urow.app_nsc() as u32 * NSC_GRANULE,
+            data_secure: urow.data_secure() as u32 * FLASH_GRANULE,
+            ram_secure: urow.ram_secure() as u32 * RAM_GRANULE,
+        }
+    }
+
+    /// Compute a layout keeping the boot region of `self`, with each
+    /// application size rounded up to its granularity, and validate it
+    pub fn with_app(
+        &self,
+        secure_flash: u32,
+        nsc_flash: u32,
+        secure_data: u32,
+        secure_ram: u32,
+    ) -> Result<Self, Error> {
+        let nsc = round_up(nsc_flash, NSC_GRANULE)?;
+        let secure_flash = secure_flash.checked_add(nsc).ok_or(Error::InvalidSize)?;
+        let layout = Layout {
+            boot_secure: self.boot_secure,
+            boot_nsc: self.boot_nsc,
+            app_secure: round_up(secure_flash, FLASH_GRANULE)?,
+            app_nsc: nsc,
+            data_secure: round_up(secure_data, FLASH_GRANULE)?,
+            ram_secure: round_up(secure_ram, RAM_GRANULE)?,
+        };
+        layout.validate()?;
+        Ok(layout)
+    }
+
+    /// Check the layout can be encoded and fits `FLASH_SIZE`,
+    /// `DATA_FLASH_SIZE` and `RAM_SIZE`
+    pub fn validate(&self) -> Result<(), Error> {
+        units(self.boot_secure, FLASH_GRANULE, BS_WIDTH)?;
+        units(self.boot_nsc, NSC_GRANULE, BNSC_WIDTH)?;
+        units(self.app_secure, FLASH_GRANULE, AS_WIDTH)?;
+        units(self.app_nsc, NSC_GRANULE, ANSC_WIDTH)?;
+        units(self.data_secure, FLASH_GRANULE, DS_WIDTH)?;
+        units(self.ram_secure, RAM_GRANULE, RS_WIDTH)?;
+
+        if self.boot_nsc > self.boot_secure || self.app_nsc > self.app_secure {
+            return Err(Error::NscTooLarge);
+        }
+        if self.boot_secure + self.app_secure > FLASH_SIZE
+            || self.data_secure > DATA_FLASH_SIZE
+            || self.ram_secure > RAM_SIZE
+        {
+            return Err(Error::DoesNotFit);
+        }
+        Ok(())
+    }
+
+    /// Update AS, ANSC, DS and RS in a user row, keeping the other fields
+    pub fn encode_user_row(&self, row: &mut UserRow) -> Result<(), Error> {
+        let app_secure = units(self.app_secure, FLASH_GRANULE, AS_WIDTH)?;
+        let app_nsc = units(self.app_nsc, NSC_GRANULE, ANSC_WIDTH)?;
+        let data_secure = units(self.data_secure, FLASH_GRANULE, DS_WIDTH)?;
+        let ram_secure = units(self.ram_secure, RAM_GRANULE, RS_WIDTH)?;
+
+        row.set_app_secure(app_secure);
+        row.set_app_nsc(app_nsc);
+        row.set_data_secure(data_secure);
+        row.set_ram_secure(ram_secure);
+        Ok(())
+    }
+
+    /// Program the application part of the layout into the user row, it
+    /// takes effect after the next reset
+    ///
+    /// The boot region is left as it is, it has to match the one the layout
+    /// was computed with.
+    pub fn program(&self, nvm: &mut Nvm) -> Result<(), Error> {
+        self.validate()?;
+        let current = Layout::read();
+        if (current.boot_secure, current.boot_nsc) != (self.boot_secure, self.boot_nsc) {
+            return Err(Error::BootMismatch);
+        }
+
+        let mut row = UserRow::read();
+        self.encode_user_row(&mut row)?;
+        nvm.write_user_row(&mut row)?;
+        Ok(())
+    }
+
+    /// Flash of the secure application, without its non-secure callable part
+    pub fn secure_flash(&self) -> Region {
+        Region {
+            origin: FLASH_ORIGIN + self.boot_secure,
+            length: self.app_secure - self.app_nsc,
+        }
+    }
+
+    /// Non-secure callable flash of the secure application, for the SG
+    /// veneers
+    pub fn nsc_flash(&self) -> Region {
+        Region {
+            origin: FLASH_ORIGIN + self.boot_secure + self.app_secure - self.app_nsc,
+            length: self.app_nsc,
+        }
+    }
+
+    /// Flash of the non-secure application
+    pub fn nonsecure_flash(&self) -> Region {
+        let secure = self.boot_secure + self.app_secure;
+        Region {
+            origin: FLASH_ORIGIN + secure,
+            length: FLASH_SIZE - secure,
+        }
+    }
+
+    /// RAM of the secure application
+    pub fn secure_ram(&self) -> Region {
+        Region {
+            origin: RAM_ORIGIN,
+            length: self.ram_secure,
+        }
+    }
+
+    /// RAM of the non-secure application
+    pub fn nonsecure_ram(&self) -> Region {
+        Region {
+            origin: RAM_ORIGIN + self.ram_secure,
+            length: RAM_SIZE - self.ram_secure,
+        }
+    }
+
+    /// Write the `memory.x` of the secure image, with an `NSC` region for
+    /// the veneers
+    pub fn write_secure_memory_x<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        write_memory_x(
+            w,
+            &[
+                ("FLASH", self.secure_flash()),
+                ("NSC", self.nsc_flash()),
+                ("RAM", self.secure_ram()),
+            ],
+        )
+    }
+
+    /// Write the `memory.x` of the non-secure image
+    pub fn write_nonsecure_memory_x<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        write_memory_x(
+            w,
+            &[
+                ("FLASH", self.nonsecure_flash()),
+                ("RAM", self.nonsecure_ram()),
+            ],
+        )
+    }
+}
+
+fn write_memory_x<W: fmt::Write>(w: &mut W, regions: &[(&str, Region)]) -> fmt::Result {
+    writeln!(w, "MEMORY")?;
+    writeln!(w, "{{")?;
+    for &(name, region) in regions {
+        writeln!(
+            w,
+            "    {} : ORIGIN = 0x{:08x}, LENGTH = {}",
+            name, region.origin, region.length
+        )?;
+    }
+    writeln!(w, "}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrayvec::ArrayString;
+
+    use nvm::ROW_WORDS;
+
+    fn layout(urow: u32, bocor: u32) -> Layout {
+        Layout::from_rows(&user_row(urow), &boot_config_row(bocor))
+    }
+
+    /// User row with `word` as its IDAU word and all other bits set
+    fn user_row(word: u32) -> UserRow {
+        let mut words = [0xffff_ffff; ROW_WORDS];
+        words[2] = word;
+        UserRow::from_words(words)
+    }
+
+    fn boot_config_row(word: u32) -> BootConfigRow {
+        let mut words = [0xffff_ffff; ROW_WORDS];
+        words[0] = word;
+        BootConfigRow::from_words(words)
+    }
+
+    #[test]
+    fn test_layout_words() {
+        // BS 0x800 with BNSC 0x40, AS 0x8000 with ANSC 0x400, DS 0x400, RS 0x2000
+        let urow = 0x8000_0000 | (64 << 24) | (4 << 16) | (32 << 8) | 128;
+        let bocor = 0xff00_0000 | (8 << 8) | 2;
+        let layout = layout(urow, bocor);
+        assert_eq!(
+            layout,
+            Layout {
+                boot_secure: 0x800,
+                boot_nsc: 0x40,
+                app_secure: 0x8000,
+                app_nsc: 0x400,
+                data_secure: 0x400,
+                ram_secure: 0x2000,
+            }
+        );
+        assert_eq!(layout.validate(), Ok(()));
+
+        // Unrelated bits, URWEN and the reserved ones, are kept
+        for &(word, encoded) in [
+            (urow, urow),
+            (0xffff_ffff, 0xc0f4_e080),
+            (0, urow & 0x7fff_ffff),
+        ]
+        .iter()
+        {
+            let mut row = user_row(word);
+            assert_eq!(layout.encode_user_row(&mut row), Ok(()));
+            assert_eq!(row, user_row(encoded));
+        }
+    }
+
+    #[test]
+    fn test_with_app() {
+        let current = layout(0, 0x0000_0802);
+        let layout = current.with_app(0x3000, 0x100, 0x200, 0x1000).unwrap();
+        assert_eq!(layout.boot_secure, 0x800);
+        assert_eq!(layout.app_secure, 0x3100);
+        assert_eq!(layout.app_nsc, 0x100);
+
+        // Rounded up to the granularity
+        let layout = current.with_app(0x2f01, 0x21, 0x1, 0x81).unwrap();
+        assert_eq!(layout.app_secure, 0x3000);
+        assert_eq!(layout.app_nsc, 0x40);
+        assert_eq!(layout.data_secure, 0x100);
+        assert_eq!(layout.ram_secure, 0x100);
+
+        assert_eq!(
+            current.with_app(FLASH_SIZE, 0, 0, 0),
+            Err(Error::InvalidSize)
+        );
+        assert_eq!(
+            current.with_app(FLASH_SIZE - 0x700, 0, 0, 0),
+            Err(Error::DoesNotFit)
+        );
+        assert_eq!(
+            current.with_app(0, 0, DATA_FLASH_SIZE + 0x100, 0),
+            Err(Error::DoesNotFit)
+        );
+        assert_eq!(current.with_app(0, 0, 0, RAM_SIZE), Err(Error::InvalidSize));
+        assert_eq!(current.with_app(0, 0x800, 0, 0), Err(Error::InvalidSize));
+
+        // Sizes that overflow when rounded up or added
+        assert_eq!(current.with_app(u32::MAX, 0, 0, 0), Err(Error::InvalidSize));
+        assert_eq!(current.with_app(0, u32::MAX, 0, 0), Err(Error::InvalidSize));
+        assert_eq!(current.with_app(0, 0, u32::MAX, 0), Err(Error::InvalidSize));
+        assert_eq!(current.with_app(0, 0, 0, u32::MAX), Err(Error::InvalidSize));
+        assert_eq!(
+            current.with_app(u32::MAX - 0x20, 0x100, 0, 0),
+            Err(Error::InvalidSize)
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut layout = layout(0, 0);
+        assert_eq!(layout.validate(), Ok(()));
+
+        layout.app_secure = 0x180;
+        assert_eq!(layout.validate(), Err(Error::InvalidSize));
+        layout.app_secure = 0x100;
+        layout.app_nsc = 0x120;
+        assert_eq!(layout.validate(), Err(Error::NscTooLarge));
+        layout.app_nsc = 0x20;
+        layout.ram_secure = RAM_SIZE - RAM_GRANULE;
+        assert_eq!(layout.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_memory_x() {
+        let layout = layout(0, 0).with_app(0x7c00, 0x400, 0, 0x2000).unwrap();
+
+        let mut secure = ArrayString::<[u8; 256]>::new();
+        layout.write_secure_memory_x(&mut secure).unwrap();
+        assert_eq!(
+            &secure[..],
+            "MEMORY\n{\n    FLASH : ORIGIN = 0x00000000, LENGTH = 31744\n    \
+             NSC : ORIGIN = 0x00007c00, LENGTH = 1024\n    \
+             RAM : ORIGIN = 0x20000000, LENGTH = 8192\n}\n"
+        );
+
+        let mut nonsecure = ArrayString::<[u8; 256]>::new();
+        layout.write_nonsecure_memory_x(&mut nonsecure).unwrap();
+        assert_eq!(
+            &nonsecure[..],
+            "MEMORY\n{\n    FLASH : ORIGIN = 0x00008000, LENGTH = 32768\n    \
+             RAM : ORIGIN = 0x20002000, LENGTH = 8192\n}\n"
+        );
+    }
+}
@@ -8,8 +8,23 @@ extern crate cortex_m;
 
 extern crate arrayvec;
 
+#[cfg(feature = "aead")]
+pub extern crate aead;
+#[cfg(feature = "cipher")]
+pub extern crate cipher;
+#[cfg(feature = "digest")]
+pub extern crate digest;
+#[cfg(feature = "getrandom")]
+pub extern crate getrandom;
+#[cfg(feature = "zeroize")]
+pub extern crate zeroize;
+
 #[cfg(feature = "rand_core")]
 extern crate rand_core;
+#[cfg(feature = "rand_core_05")]
+extern crate rand_core_05;
+#[cfg(feature = "rand_core_06")]
+extern crate rand_core_06;
 
 extern crate nb;
 
@@ -20,6 +35,7 @@ extern crate crypto as test_crypto;
 extern crate hex_literal;
 
 pub mod gpio;
+pub mod nsc;
 pub mod rng;
 pub mod sercom;
 
@@ -28,4 +44,18 @@ pub mod delay;
 pub mod prelude;
 pub mod time;
 
+// The ROM crypto library can only be called from the secure world
+#[cfg(not(feature = "nonsecure"))]
 pub mod crypto;
+
+// Security configuration and secure-only peripherals
+#[cfg(not(feature = "nonsecure"))]
+pub mod idau;
+#[cfg(not(feature = "nonsecure"))]
+pub mod nvm;
+#[cfg(not(feature = "nonsecure"))]
+pub mod pac;
+#[cfg(not(feature = "nonsecure"))]
+pub mod security;
+#[cfg(not(feature = "nonsecure"))]
+pub mod tram;
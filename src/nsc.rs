@@ -0,0 +1,396 @@
+//! Non-secure callable (NSC) gateway to secure services
+//!
+//! The secure image exposes services to the non-secure application through
+//! `cmse_nonsecure_entry` functions, which the linker places behind `SG`
+//! veneers in the NSC region. Every entry returns a `u32` status, `STATUS_OK`
+//! or an `Error` code, and only takes arguments that fit in r0-r3.
+//!
+//! Secure side, the application crate needs `#![feature(cmse_nonsecure_entry)]`:
+//! ```ignore
+//! hal::nsc::install_rng(Rng::new(p.TRNG, &mut p.MCLK));
+//!
+//! hal::nsc_services!();
+//!
+//! hal::nsc_entry! {
+//!     fn nsc_led_on() {
+//!         led.set_high();
+//!         Ok(())
+//!     }
+//! }
+//! ```
+//! The non-secure application links against the import library of the secure
+//! image (`--cmse-implib`) and calls the services through `nsc::client`.
+
+#[cfg(not(feature = "nonsecure"))]
+use core::cell::RefCell;
+#[cfg(not(feature = "nonsecure"))]
+use core::slice;
+
+#[cfg(all(not(feature = "nonsecure"), not(test), target_arch = "arm"))]
+use core::arch::asm;
+
+#[cfg(all(not(feature = "nonsecure"), not(test)))]
+use cortex_m::cmse::{AccessType, TestTarget};
+#[cfg(not(feature = "nonsecure"))]
+use cortex_m::interrupt::{self, Mutex};
+#[cfg(all(not(feature = "nonsecure"), not(test)))]
+use cortex_m::peripheral::scb::VectActive;
+#[cfg(all(not(feature = "nonsecure"), not(test)))]
+use cortex_m::peripheral::SCB;
+#[cfg(all(not(feature = "nonsecure"), not(test)))]
+use cortex_m::register::control::{Control, Npriv};
+#[cfg(not(feature = "nonsecure"))]
+use hal::blocking::rng::Read;
+
+#[cfg(not(feature = "nonsecure"))]
+use crypto::Sha256;
+#[cfg(not(feature = "nonsecure"))]
+use rng::Rng;
+
+/// Status returned by a gateway entry that succeeded
+pub const STATUS_OK: u32 = 0;
+
+/// Length of the digest written by the `nsc_sha256` service
+pub const SHA256_DIGEST_LEN: usize = 32;
+
+/// Gateway errors, passed to the non-secure side as a `u32` status
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(u32)]
+pub enum Error {
+    /// A pointer argument does not refer to memory the non-secure caller
+    /// may access
+    InvalidPointer = 1,
+    /// The secure image has not set up the service
+    Unavailable = 2,
+    /// The TRNG health tests failed
+    RngFailure = 3,
+    /// The status is not one this version of the gateway knows about
+    Unknown = 0xffff_ffff,
+}
+
+impl Error {
+    /// Status code passed back to the non-secure caller
+    pub fn status(self) -> u32 {
+        self as u32
+    }
+}
+
+/// Encode the result of a service as a gateway status
+pub fn status(result: Result<(), Error>) -> u32 {
+    match result {
+        Ok(()) => STATUS_OK,
+        Err(err) => err.status(),
+    }
+}
+
+/// Decode a gateway status
+pub fn from_status(status: u32) -> Result<(), Error> {
+    match status {
+        STATUS_OK => Ok(()),
+        1 => Err(Error::InvalidPointer),
+        2 => Err(Error::Unavailable),
+        3 => Err(Error::RngFailure),
+        _ => Err(Error::Unknown),
+    }
+}
+
+/// CONTROL register of the non-secure state
+#[cfg(all(not(feature = "nonsecure"), not(test), target_arch = "arm"))]
+fn control_ns() -> Control {
+    let bits: u32;
+    unsafe {
+        asm!("mrs {}, CONTROL_NS", out(reg) bits, options(nomem, nostack, preserves_flags));
+    }
+    Control::from_bits(bits)
+}
+
+/// Only the Cortex-M has a CONTROL_NS, elsewhere the caller is taken to be
+/// unprivileged
+#[cfg(all(not(feature = "nonsecure"), not(test), not(target_arch = "arm")))]
+fn control_ns() -> Control {
+    Control::from_bits(1)
+}
+
+/// Whether the non-secure caller runs unprivileged, i.e. in thread mode with
+/// CONTROL_NS.nPRIV set
+#[cfg(all(not(feature = "nonsecure"), not(test)))]
+fn ns_caller_unprivileged() -> bool {
+    SCB::vect_active() == VectActive::ThreadMode && control_ns().npriv() == Npriv::Unprivileged
+}
+
+/// Check that `len` bytes at `addr` are non-secure memory the caller may
+/// read, or also write, using the TT instruction
+///
+/// The non-secure MPU is checked with the privilege of the caller, so an
+/// unprivileged caller cannot pass memory only privileged code may access.
+#[cfg(all(not(feature = "nonsecure"), not(test)))]
+fn ns_accessible(addr: usize, len: usize, write: bool) -> bool {
+    let access = if ns_caller_unprivileged() {
+        AccessType::NonSecureUnprivileged
+    } else {
+        AccessType::NonSecure
+    };
+    match TestTarget::check_range(addr as *mut u32, len, access) {
+        Some(target) if write => target.ns_read_and_writable(),
+        Some(target) => target.ns_readable(),
+        None => false,
+    }
+}
+
+/// Host side stand in for the TT instruction, `SECURE_MEMORY` is the only
+/// memory that is not non-secure
+#[cfg(all(test, not(feature = "nonsecure")))]
+static SECURE_MEMORY: [u8; 64] = [0u8; 64];
+
+#[cfg(all(test, not(feature = "nonsecure")))]
+fn ns_accessible(addr: usize, len: usize, _write: bool) -> bool {
+    let secure = SECURE_MEMORY.as_ptr() as usize;
+    addr + len <= secure || addr >= secure + SECURE_MEMORY.len()
+}
+
+/// Validate a buffer passed in by the non-secure caller
+#[cfg(not(feature = "nonsecure"))]
+fn check_ns_range(addr: usize, len: usize, write: bool) -> Result<(), Error> {
+    if addr == 0 || addr.checked_add(len).is_none() || !ns_accessible(addr, len, write) {
+        return Err(Error::InvalidPointer);
+    }
+    Ok(())
+}
+
+/// Borrow a buffer of the non-secure caller after checking that the whole
+/// range is non-secure memory it may read
+///
+/// # Safety
+///
+/// Non-secure code may still change the buffer while it is borrowed, e.g.
+/// from an interrupt handler, the secure code must not rely on it staying
+/// the same.
+#[cfg(not(feature = "nonsecure"))]
+pub unsafe fn ns_slice<'a>(ptr: *const u8, len: usize) -> Result<&'a [u8], Error> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    check_ns_range(ptr as usize, len, false)?;
+    Ok(slice::from_raw_parts(ptr, len))
+}
+
+/// Mutably borrow a buffer of the non-secure caller after checking that the
+/// whole range is non-secure memory it may write
+///
+/// # Safety
+///
+/// See `ns_slice`.
+#[cfg(not(feature = "nonsecure"))]
+pub unsafe fn ns_slice_mut<'a>(ptr: *mut u8, len: usize) -> Result<&'a mut [u8], Error> {
+    if len == 0 {
+        return Ok(&mut []);
+    }
+    check_ns_range(ptr as usize, len, true)?;
+    Ok(slice::from_raw_parts_mut(ptr, len))
+}
+
+#[cfg(not(feature = "nonsecure"))]
+static NSC_RNG: Mutex<RefCell<Option<Rng>>> = Mutex::new(RefCell::new(None));
+
+/// Install the `Rng` behind the `nsc_rng_fill` service, returning the
+/// previous one
+#[cfg(not(feature = "nonsecure"))]
+pub fn install_rng(rng: Rng) -> Option<Rng> {
+    interrupt::free(|cs| NSC_RNG.borrow(cs).replace(Some(rng)))
+}
+
+/// Remove the `Rng` behind the `nsc_rng_fill` service
+#[cfg(not(feature = "nonsecure"))]
+pub fn uninstall_rng() -> Option<Rng> {
+    interrupt::free(|cs| NSC_RNG.borrow(cs).borrow_mut().take())
+}
+
+/// `nsc_sha256` service, hash `len` bytes at `data` with the ROM `Sha256`
+/// and write the digest to `digest`
+///
+/// # Safety
+///
+/// See `ns_slice`.
+#[cfg(not(feature = "nonsecure"))]
+pub unsafe fn sha256_service(data: *const u8, len: usize, digest: *mut u8) -> Result<(), Error> {
+    let data = ns_slice(data, len)?;
+    let digest = ns_slice_mut(digest, SHA256_DIGEST_LEN)?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    digest.copy_from_slice(&hasher.finalize());
+    Ok(())
+}
+
+/// `nsc_rng_fill` service, fill `len` bytes at `buffer` from the installed
+/// `Rng`
+///
+/// The TRNG is read with interrupts disabled.
+///
+/// # Safety
+///
+/// See `ns_slice`.
+#[cfg(not(feature = "nonsecure"))]
+pub unsafe fn rng_fill_service(buffer: *mut u8, len: usize) -> Result<(), Error> {
+    let buffer = ns_slice_mut(buffer, len)?;
+
+    interrupt::free(|cs| fill_from(NSC_RNG.borrow(cs), buffer))
+}
+
+/// Fill `buffer` from the `Rng` in `rng`, if one is installed
+#[cfg(not(feature = "nonsecure"))]
+fn fill_from(rng: &RefCell<Option<Rng>>, buffer: &mut [u8]) -> Result<(), Error> {
+    match rng.borrow_mut().as_mut() {
+        Some(rng) => rng.read(buffer).map_err(|_| Error::RngFailure),
+        None => Err(Error::Unavailable),
+    }
+}
+
+/// Define a non-secure callable entry function
+///
+/// The body evaluates to `Result<(), nsc::Error>`, which is returned to the
+/// caller as a `u32` status. Pointer arguments must be checked with
+/// `nsc::ns_slice` or `nsc::ns_slice_mut` before they are used.
+#[cfg(not(feature = "nonsecure"))]
+#[macro_export]
+macro_rules! nsc_entry {
+    ($(#[$attr:meta])* fn $name:ident($($arg:ident: $ty:ty),*) $body:block) => {
+        $(#[$attr])*
+        #[no_mangle]
+        #[cmse_nonsecure_entry]
+        pub extern "C" fn $name($($arg: $ty),*) -> u32 {
+            let result: Result<(), $crate::nsc::Error> = (|| $body)();
+            $crate::nsc::status(result)
+        }
+    };
+}
+
+/// Define the entry functions of the built-in services, `nsc_sha256` and
+/// `nsc_rng_fill`, invoke once in the secure application crate
+#[cfg(not(feature = "nonsecure"))]
+#[macro_export]
+macro_rules! nsc_services {
+    () => {
+        $crate::nsc_entry! {
+            fn nsc_sha256(data: *const u8, len: usize, digest: *mut u8) {
+                unsafe { $crate::nsc::sha256_service(data, len, digest) }
+            }
+        }
+
+        $crate::nsc_entry! {
+            fn nsc_rng_fill(buffer: *mut u8, len: usize) {
+                unsafe { $crate::nsc::rng_fill_service(buffer, len) }
+            }
+        }
+    };
+}
+
+/// Non-secure side stubs for the built-in services
+#[cfg(feature = "nonsecure")]
+pub mod client {
+    use hal::blocking::rng::Read;
+
+    use super::{from_status, Error, SHA256_DIGEST_LEN};
+
+    extern "C" {
+        fn nsc_sha256(data: *const u8, len: usize, digest: *mut u8) -> u32;
+        fn nsc_rng_fill(buffer: *mut u8, len: usize) -> u32;
+    }
+
+    /// Hash `data` with the SHA-256 service of the secure image
+    pub fn sha256(data: &[u8]) -> Result<[u8; SHA256_DIGEST_LEN], Error> {
+        let mut digest = [0u8; SHA256_DIGEST_LEN];
+        from_status(unsafe { nsc_sha256(data.as_ptr(), data.len(), digest.as_mut_ptr()) })?;
+        Ok(digest)
+    }
+
+    /// Random number generator backed by the `Rng` of the secure image
+    pub struct NscRng;
+
+    impl Read for NscRng {
+        type Error = Error;
+
+        fn read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+            from_status(unsafe { nsc_rng_fill(buffer.as_mut_ptr(), buffer.len()) })
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "nonsecure")))]
+mod tests {
+    use super::*;
+
+    use core::ptr;
+
+    #[test]
+    fn test_status_round_trip() {
+        assert_eq!(status(Ok(())), STATUS_OK);
+        for &err in [Error::InvalidPointer, Error::Unavailable, Error::RngFailure].iter() {
+            assert_eq!(from_status(status(Err(err))), Err(err));
+        }
+        assert_eq!(from_status(0x8000), Err(Error::Unknown));
+    }
+
+    #[test]
+    fn test_ns_slice() {
+        let buffer = [1u8, 2, 3, 4];
+        unsafe {
+            assert_eq!(ns_slice(buffer.as_ptr(), 4), Ok(&buffer[..]));
+            assert_eq!(ns_slice(buffer.as_ptr(), 0), Ok(&[][..]));
+            assert_eq!(ns_slice(ptr::null(), 4), Err(Error::InvalidPointer));
+            assert_eq!(
+                ns_slice(buffer.as_ptr(), usize::MAX),
+                Err(Error::InvalidPointer)
+            );
+
+            // Inside, straddling the start and straddling the end of secure memory
+            let secure = SECURE_MEMORY.as_ptr();
+            assert_eq!(ns_slice(secure.add(8), 4), Err(Error::InvalidPointer));
+            assert_eq!(ns_slice(secure.sub(2), 4), Err(Error::InvalidPointer));
+            assert_eq!(ns_slice(secure.add(62), 4), Err(Error::InvalidPointer));
+            assert!(ns_slice(secure.sub(4), 4).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_sha256_service() {
+        let data = b"abc";
+        let mut digest = [0u8; SHA256_DIGEST_LEN];
+        assert_eq!(
+            unsafe { sha256_service(data.as_ptr(), data.len(), digest.as_mut_ptr()) },
+            Ok(())
+        );
+        assert_eq!(
+            digest,
+            hex!("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+    }
+
+    #[test]
+    fn test_sha256_service_invalid_pointer() {
+        let data = b"abc";
+        let mut digest = [0u8; SHA256_DIGEST_LEN];
+        assert_eq!(
+            unsafe { sha256_service(SECURE_MEMORY.as_ptr(), 16, digest.as_mut_ptr()) },
+            Err(Error::InvalidPointer)
+        );
+        assert_eq!(
+            unsafe { sha256_service(data.as_ptr(), data.len(), SECURE_MEMORY.as_ptr() as *mut u8) },
+            Err(Error::InvalidPointer)
+        );
+        assert_eq!(digest, [0u8; SHA256_DIGEST_LEN]);
+    }
+
+    #[test]
+    fn test_rng_fill_service() {
+        let mut buffer = [0u8; 16];
+        assert_eq!(
+            fill_from(&RefCell::new(None), &mut buffer),
+            Err(Error::Unavailable)
+        );
+        assert_eq!(
+            unsafe { rng_fill_service(SECURE_MEMORY.as_ptr() as *mut u8, 16) },
+            Err(Error::InvalidPointer)
+        );
+    }
+}
@@ -0,0 +1,698 @@
+//! NVM user row and boot configuration row access through NVMCTRL
+//!
+//! Both rows are 256 bytes in the auxiliary NVM space. `UserRow` and
+//! `BootConfigRow` are copies of a row with typed accessors for its fields,
+//! all other bits, reserved ones included, are kept as they were read.
+//!
+//! The boot ROM checks the user row against the CRC32 in `USER_CRC_WORD`,
+//! and with BOOTOPT set the boot configuration row against the SHA-256 from
+//! `BOCOR_HASH_WORD` on. `Nvm` updates both when writing a row.
+
+use core::ptr;
+
+use atsaml11xxx::NVMCTRL_SEC;
+
+use crypto::Sha256;
+
+/// Address of the user row (UROW)
+pub const USER_ROW_ADDR: u32 = 0x0080_4000;
+/// Address of the boot configuration row (BOCOR)
+pub const BOOT_CONFIG_ROW_ADDR: u32 = 0x0080_C000;
+/// Number of 32 bit words in a row
+pub const ROW_WORDS: usize = 64;
+/// Word of the user row holding the CRC32 over the words before it
+/// (USERCRC, bits 223:192)
+pub const USER_CRC_WORD: usize = 6;
+/// First of the words of the boot configuration row holding the SHA-256
+/// over the words before it
+pub const BOCOR_HASH_WORD: usize = 56;
+
+/// Number of 32 bit words in a page, a row has four
+const PAGE_WORDS: usize = 16;
+
+/// First words of the chip erase keys CEKEY0 to CEKEY2
+const CEKEY_WORDS: [usize; 3] = [4, 8, 12];
+/// First word of the CRC key
+const CRCKEY_WORD: usize = 16;
+/// First word of the secure boot key
+const BOOTKEY_WORD: usize = 20;
+
+/// NVMCTRL CTRLA command execution key
+const CMDEX_KEY: u8 = 0xa5;
+/// Erase auxiliary row
+const CMD_EAR: u8 = 0x05;
+/// Write auxiliary page
+const CMD_WAP: u8 = 0x06;
+/// Page buffer clear
+const CMD_PBC: u8 = 0x44;
+
+/// NVM programming errors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// The NVM controller reported a programming error
+    Programming,
+    /// The row is locked, e.g. the boot configuration row once BCWEN is
+    /// cleared
+    Locked,
+    /// The row read back differs from what was written
+    Verify,
+}
+
+/// CRC32 (IEEE 802.3) over the little endian bytes of `words`
+pub fn crc32(words: &[u32]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for word in words {
+        for &byte in word.to_le_bytes().iter() {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                let mask = (crc & 1).wrapping_neg();
+                crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+            }
+        }
+    }
+    !crc
+}
+
+fn read_row(addr: u32) -> [u32; ROW_WORDS] {
+    let mut row = [0u32; ROW_WORDS];
+    for (i, word) in row.iter_mut().enumerate() {
+        *word = unsafe { ptr::read_volatile((addr as *const u32).add(i)) };
+    }
+    row
+}
+
+/// Conversion between field values and their bits
+trait FieldValue {
+    fn from_bits(bits: u32) -> Self;
+    fn into_bits(self) -> u32;
+}
+
+impl FieldValue for bool {
+    fn from_bits(bits: u32) -> Self {
+        bits != 0
+    }
+
+    fn into_bits(self) -> u32 {
+        self as u32
+    }
+}
+
+impl FieldValue for u8 {
+    fn from_bits(bits: u32) -> Self {
+        bits as u8
+    }
+
+    fn into_bits(self) -> u32 {
+        self as u32
+    }
+}
+
+impl FieldValue for u32 {
+    fn from_bits(bits: u32) -> Self {
+        bits
+    }
+
+    fn into_bits(self) -> u32 {
+        self
+    }
+}
+
+/// BOD33 action when the supply drops below the threshold
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bod33Action {
+    None,
+    Reset,
+    Interrupt,
+    Reserved,
+}
+
+impl FieldValue for Bod33Action {
+    fn from_bits(bits: u32) -> Self {
+        match bits {
+            0 => Bod33Action::None,
+            1 => Bod33Action::Reset,
+            2 => Bod33Action::Interrupt,
+            _ => Bod33Action::Reserved,
+        }
+    }
+
+    fn into_bits(self) -> u32 {
+        self as u32
+    }
+}
+
+fn field_mask(width: u32) -> u32 {
+    if width == 32 {
+        0xffff_ffff
+    } else {
+        (1 << width) - 1
+    }
+}
+
+/// Getters and setters of the fields of a row, `(word, offset, width)`
+///
+/// Setters truncate the value to the field width and leave the other bits
+/// untouched.
+macro_rules! row_fields {
+    ($Row:ident {
+        $($(#[$doc:meta])* ($get:ident, $set:ident): $T:ty = ($word:expr, $offset:expr, $width:expr),)+
+    }) => {
+        impl $Row {
+            $(
+                $(#[$doc])*
+                pub fn $get(&self) -> $T {
+                    <$T as FieldValue>::from_bits((self.words[$word] >> $offset) & field_mask($width))
+                }
+
+                $(#[$doc])*
+                pub fn $set(&mut self, value: $T) {
+                    let mask = field_mask($width) << $offset;
+                    let bits = (FieldValue::into_bits(value) << $offset) & mask;
+                    self.words[$word] = (self.words[$word] & !mask) | bits;
+                }
+            )+
+        }
+    };
+}
+
+/// Copy of the user row
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserRow {
+    words: [u32; ROW_WORDS],
+}
+
+impl UserRow {
+    /// Read the user row
+    pub fn read() -> Self {
+        UserRow::from_words(read_row(USER_ROW_ADDR))
+    }
+
+    pub fn from_words(words: [u32; ROW_WORDS]) -> Self {
+        UserRow { words }
+    }
+
+    pub fn words(&self) -> &[u32; ROW_WORDS] {
+        &self.words
+    }
+
+    /// CRC32 the boot ROM checks the row against
+    pub fn crc(&self) -> u32 {
+        self.words[USER_CRC_WORD]
+    }
+
+    /// Whether the stored CRC matches the row
+    pub fn crc_valid(&self) -> bool {
+        self.crc() == crc32(&self.words[..USER_CRC_WORD])
+    }
+
+    fn update_crc(&mut self) {
+        self.words[USER_CRC_WORD] = crc32(&self.words[..USER_CRC_WORD]);
+    }
+}
+
+row_fields!(UserRow {
+    /// Secure flash region unlock bits (SULCK)
+    (sulck, set_sulck): u8 = (0, 0, 3),
+    /// Non-secure flash region unlock bits (NSULCK)
+    (nsulck, set_nsulck): u8 = (0, 3, 3),
+    /// BOD33 threshold level at reset
+    (bod33_level, set_bod33_level): u8 = (0, 7, 6),
+    /// BOD33 disabled at reset
+    (bod33_disable, set_bod33_disable): bool = (0, 13, 1),
+    /// BOD33 action at reset
+    (bod33_action, set_bod33_action): Bod33Action = (0, 14, 2),
+    /// WDT runs in standby at reset
+    (wdt_run_standby, set_wdt_run_standby): bool = (0, 25, 1),
+    /// WDT enabled at reset
+    (wdt_enable, set_wdt_enable): bool = (0, 26, 1),
+    /// WDT always-on at reset
+    (wdt_always_on, set_wdt_always_on): bool = (0, 27, 1),
+    /// WDT time-out period at reset
+    (wdt_period, set_wdt_period): u8 = (0, 28, 4),
+    /// WDT window period at reset
+    (wdt_window, set_wdt_window): u8 = (1, 0, 4),
+    /// WDT early warning interrupt offset at reset
+    (wdt_ewoffset, set_wdt_ewoffset): u8 = (1, 4, 4),
+    /// WDT window mode enabled at reset
+    (wdt_window_enable, set_wdt_window_enable): bool = (1, 8, 1),
+    /// BOD33 hysteresis enabled at reset
+    (bod33_hysteresis, set_bod33_hysteresis): bool = (1, 9, 1),
+    /// RAM is execute never (RXN)
+    (ram_execute_never, set_ram_execute_never): bool = (1, 11, 1),
+    /// Data flash is execute never (DXN)
+    (data_flash_execute_never, set_data_flash_execute_never): bool = (1, 12, 1),
+    /// Secure application flash (AS), in 256 byte units
+    (app_secure, set_app_secure): u8 = (2, 0, 8),
+    /// Non-secure callable application flash (ANSC), in 32 byte units
+    (app_nsc, set_app_nsc): u8 = (2, 8, 6),
+    /// Secure data flash (DS), in 256 byte units
+    (data_secure, set_data_secure): u8 = (2, 16, 4),
+    /// Secure RAM (RS), in 128 byte units
+    (ram_secure, set_ram_secure): u8 = (2, 24, 7),
+    /// User row write enable (URWEN), once cleared the user row is locked
+    (urwen, set_urwen): bool = (2, 31, 1),
+    /// Bridge A peripherals that are non-secure at reset
+    (nonseca, set_nonseca): u32 = (3, 0, 32),
+    /// Bridge B peripherals that are non-secure at reset
+    (nonsecb, set_nonsecb): u32 = (4, 0, 32),
+    /// Bridge C peripherals that are non-secure at reset
+    (nonsecc, set_nonsecc): u32 = (5, 0, 32),
+});
+
+/// Copy of the boot configuration row
+#[derive(Clone, Debug, PartialEq)]
+pub struct BootConfigRow {
+    words: [u32; ROW_WORDS],
+}
+
+impl BootConfigRow {
+    /// Read the boot configuration row
+    pub fn read() -> Self {
+        BootConfigRow::from_words(read_row(BOOT_CONFIG_ROW_ADDR))
+    }
+
+    pub fn from_words(words: [u32; ROW_WORDS]) -> Self {
+        BootConfigRow { words }
+    }
+
+    pub fn words(&self) -> &[u32; ROW_WORDS] {
+        &self.words
+    }
+
+    /// Chip erase key `n`, 0 to 2
+    pub fn cekey(&self, n: usize) -> [u8; 16] {
+        self.bytes(CEKEY_WORDS[n])
+    }
+
+    /// Set chip erase key `n`, 0 to 2
+    pub fn set_cekey(&mut self, n: usize, key: &[u8; 16]) {
+        self.set_bytes(CEKEY_WORDS[n], key);
+    }
+
+    /// Key of the boot ROM CRC command
+    pub fn crckey(&self) -> [u8; 16] {
+        self.bytes(CRCKEY_WORD)
+    }
+
+    /// Set the key of the boot ROM CRC command
+    pub fn set_crckey(&mut self, key: &[u8; 16]) {
+        self.set_bytes(CRCKEY_WORD, key);
+    }
+
+    /// Secure boot key
+    pub fn bootkey(&self) -> [u8; 32] {
+        self.bytes(BOOTKEY_WORD)
+    }
+
+    /// Set the secure boot key
+    pub fn set_bootkey(&mut self, key: &[u8; 32]) {
+        self.set_bytes(BOOTKEY_WORD, key);
+    }
+
+    /// SHA-256 the boot ROM checks the row against (BOCORHASH)
+    pub fn hash(&self) -> [u8; 32] {
+        self.bytes(BOCOR_HASH_WORD)
+    }
+
+    /// Whether the stored hash matches the row
+    pub fn hash_valid(&self) -> bool {
+        self.hash() == self.compute_hash()
+    }
+
+    fn compute_hash(&self) -> [u8; 32] {
+        let mut sha = Sha256::new();
+        for word in self.words[..BOCOR_HASH_WORD].iter() {
+            sha.update(&word.to_le_bytes());
+        }
+        sha.finalize()
+    }
+
+    fn update_hash(&mut self) {
+        let hash = self.compute_hash();
+        self.set_bytes(BOCOR_HASH_WORD, &hash);
+    }
+
+    fn bytes<B: AsMut<[u8]> + Default>(&self, word: usize) -> B {
+        let mut bytes = B::default();
+        for (chunk, w) in bytes.as_mut().chunks_mut(4).zip(self.words[word..].iter()) {
+            chunk.copy_from_slice(&w.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn set_bytes(&mut self, word: usize, bytes: &[u8]) {
+        for (chunk, w) in bytes.chunks(4).zip(self.words[word..].iter_mut()) {
+            *w = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+    }
+}
+
+row_fields!(BootConfigRow {
+    /// Non-secure callable boot flash (BNSC), in 32 byte units
+    (boot_nsc, set_boot_nsc): u8 = (0, 0, 6),
+    /// Secure boot flash (BS), in 256 byte units
+    (boot_secure, set_boot_secure): u8 = (0, 8, 8),
+    /// Boot flash protected from writes (BOOTPROT), in 256 byte units
+    (bootprot, set_bootprot): u8 = (0, 16, 8),
+    /// Secure boot option (BOOTOPT), 0 skips the boot image verification
+    (bootopt, set_bootopt): u8 = (1, 0, 8),
+    /// Boot configuration row write enable (BCWEN)
+    (bcwen, set_bcwen): bool = (1, 8, 1),
+    /// Boot configuration row read enable (BCREN)
+    (bcren, set_bcren): bool = (1, 9, 1),
+});
+
+/// NVM controller, used to program the auxiliary rows
+pub struct Nvm {
+    nvmctrl: NVMCTRL_SEC,
+}
+
+impl Nvm {
+    pub fn new(nvmctrl: NVMCTRL_SEC) -> Self {
+        Nvm { nvmctrl }
+    }
+
+    /// Erase and rewrite the user row with `row`, after updating its CRC
+    ///
+    /// Changes take effect after the next reset.
+    pub fn write_user_row(&mut self, row: &mut UserRow) -> Result<(), Error> {
+        row.update_crc();
+        self.write_row(USER_ROW_ADDR, &row.words)
+    }
+
+    /// Read the user row, change it with `f` and write it back
+    pub fn modify_user_row<F: FnOnce(&mut UserRow)>(&mut self, f: F) -> Result<(), Error> {
+        let mut row = UserRow::read();
+        f(&mut row);
+        self.write_user_row(&mut row)
+    }
+
+    /// Erase and rewrite the boot configuration row with `row`, after
+    /// updating its hash
+    ///
+    /// Fails with `Error::Locked` once BCWEN is cleared. Changes take effect
+    /// after the next reset.
+    pub fn write_boot_config_row(&mut self, row: &mut BootConfigRow) -> Result<(), Error> {
+        row.update_hash();
+        self.write_row(BOOT_CONFIG_ROW_ADDR, &row.words)
+    }
+
+    /// Read the boot configuration row, change it with `f` and write it back
+    pub fn modify_boot_config_row<F: FnOnce(&mut BootConfigRow)>(
+        &mut self,
+        f: F,
+    ) -> Result<(), Error> {
+        let mut row = BootConfigRow::read();
+        f(&mut row);
+        self.write_boot_config_row(&mut row)
+    }
+
+    fn write_row(&mut self, addr: u32, row: &[u32; ROW_WORDS]) -> Result<(), Error> {
+        self.command(addr, CMD_EAR)?;
+
+        for (page, words) in row.chunks(PAGE_WORDS).enumerate() {
+            let page_addr = addr + (page * PAGE_WORDS * 4) as u32;
+            self.command(page_addr, CMD_PBC)?;
+            for (i, &word) in words.iter().enumerate() {
+                unsafe { ptr::write_volatile((page_addr as *mut u32).add(i), word) };
+            }
+            self.command(page_addr, CMD_WAP)?;
+        }
+
+        if read_row(addr)[..] != row[..] {
+            return Err(Error::Verify);
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, addr: u32, cmd: u8) -> Result<(), Error> {
+        while self.nvmctrl.status.read().ready().bit_is_clear() {}
+        // Clear stale error flags
+        self.nvmctrl
+            .intflag
+            .write(|w| w.proge().set_bit().locke().set_bit().nvme().set_bit());
+
+        self.nvmctrl.addr.write(|w| unsafe { w.addr().bits(addr) });
+        self.nvmctrl
+            .ctrla
+            .write(|w| unsafe { w.cmd().bits(cmd).cmdex().bits(CMDEX_KEY) });
+        while self.nvmctrl.status.read().ready().bit_is_clear() {}
+
+        let flags = self.nvmctrl.intflag.read();
+        if flags.locke().bit_is_set() {
+            Err(Error::Locked)
+        } else if flags.proge().bit_is_set() || flags.nvme().bit_is_set() {
+            Err(Error::Programming)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn free(self) -> NVMCTRL_SEC {
+        self.nvmctrl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Row image with a mix of set and cleared bits in every word
+    fn pattern() -> [u32; ROW_WORDS] {
+        let mut words = [0u32; ROW_WORDS];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = 0xa5a5_5a5a ^ (i as u32).wrapping_mul(0x9e37_79b9);
+        }
+        words
+    }
+
+    /// User row with the datasheet fuse defaults, the CRC computed with zlib
+    ///
+    /// The BOD12 calibration in word 0 bits 24:16 is factory programmed per
+    /// device, it is left at zero here.
+    const DEFAULT_USER_ROW: [u32; 8] = [
+        0xb000_433f,
+        0x0000_18bb,
+        0x8000_0000,
+        0x0000_0000,
+        0x0000_0000,
+        0x0000_0000,
+        0x45e9_e9d6,
+        0xffff_ffff,
+    ];
+
+    fn default_user_row() -> UserRow {
+        let mut words = [0xffff_ffff; ROW_WORDS];
+        words[..DEFAULT_USER_ROW.len()].copy_from_slice(&DEFAULT_USER_ROW);
+        UserRow::from_words(words)
+    }
+
+    /// Check that `words` only differs from `pattern()` in the bits of
+    /// `changed` in `word`
+    fn assert_changed(words: &[u32; ROW_WORDS], word: usize, changed: u32) {
+        for (i, (&new, &old)) in words.iter().zip(pattern().iter()).enumerate() {
+            let allowed = if i == word { changed } else { 0 };
+            assert_eq!((new ^ old) & !allowed, 0, "word {}", i);
+        }
+    }
+
+    #[test]
+    fn test_crc32() {
+        // Matches zlib.crc32(b"12345678")
+        let words = [u32::from_le_bytes(*b"1234"), u32::from_le_bytes(*b"5678")];
+        assert_eq!(crc32(&words), 0x9ae0_daaf);
+        assert_eq!(crc32(&[]), 0);
+    }
+
+    #[test]
+    fn test_user_row_fields() {
+        let mut row = UserRow::from_words([0; ROW_WORDS]);
+        row.set_sulck(0b111);
+        row.set_bod33_level(0x3f);
+        row.set_bod33_action(Bod33Action::Interrupt);
+        row.set_wdt_enable(true);
+        row.set_wdt_period(0xb);
+        row.set_wdt_ewoffset(0x5);
+        row.set_wdt_window_enable(true);
+        row.set_ram_execute_never(true);
+        row.set_data_flash_execute_never(true);
+        row.set_app_secure(0x80);
+        row.set_ram_secure(0x7f);
+        row.set_urwen(true);
+        row.set_nonsecc(0xdead_beef);
+
+        // Bit positions of the datasheet NVM user row mapping
+        assert_eq!(
+            row.words()[0],
+            0b111 | (0x3f << 7) | (2 << 14) | (1 << 26) | (0xb << 28)
+        );
+        // Bit 10 of word 1 is reserved
+        assert_eq!(
+            row.words()[1],
+            (0x5 << 4) | (1 << 8) | (1 << 11) | (1 << 12)
+        );
+        assert_eq!(row.words()[2], 0x80 | (0x7f << 24) | (1 << 31));
+        assert_eq!(row.words()[5], 0xdead_beef);
+
+        let row = UserRow::from_words(*row.words());
+        assert_eq!(row.sulck(), 0b111);
+        assert_eq!(row.nsulck(), 0);
+        assert_eq!(row.bod33_level(), 0x3f);
+        assert_eq!(row.bod33_action(), Bod33Action::Interrupt);
+        assert!(row.wdt_enable());
+        assert!(!row.wdt_always_on());
+        assert_eq!(row.wdt_period(), 0xb);
+        assert_eq!(row.wdt_window(), 0);
+        assert_eq!(row.wdt_ewoffset(), 0x5);
+        assert!(row.wdt_window_enable());
+        assert!(!row.bod33_hysteresis());
+        assert!(row.ram_execute_never());
+        assert!(row.data_flash_execute_never());
+        assert_eq!(row.app_secure(), 0x80);
+        assert_eq!(row.ram_secure(), 0x7f);
+        assert!(row.urwen());
+        assert_eq!(row.nonsecc(), 0xdead_beef);
+    }
+
+    #[test]
+    fn test_default_user_row_fields() {
+        let row = default_user_row();
+        assert_eq!(row.sulck(), 0x7);
+        assert_eq!(row.nsulck(), 0x7);
+        assert_eq!(row.bod33_level(), 0x6);
+        assert!(!row.bod33_disable());
+        assert_eq!(row.bod33_action(), Bod33Action::Reset);
+        assert!(!row.wdt_run_standby());
+        assert!(!row.wdt_enable());
+        assert!(!row.wdt_always_on());
+        assert_eq!(row.wdt_period(), 0xb);
+        assert_eq!(row.wdt_window(), 0xb);
+        assert_eq!(row.wdt_ewoffset(), 0xb);
+        assert!(!row.wdt_window_enable());
+        assert!(!row.bod33_hysteresis());
+        assert!(row.ram_execute_never());
+        assert!(row.data_flash_execute_never());
+        assert_eq!(row.app_secure(), 0);
+        assert_eq!(row.ram_secure(), 0);
+        assert!(row.urwen());
+        assert_eq!(row.nonseca(), 0);
+    }
+
+    #[test]
+    fn test_user_row_preserves_bits() {
+        let mut row = UserRow::from_words(pattern());
+        let level = row.bod33_level();
+        row.set_bod33_level(!level);
+        assert_eq!(row.bod33_level(), !level & 0x3f);
+        assert_changed(row.words(), 0, 0x3f << 7);
+        row.set_bod33_level(level);
+        assert_eq!(row.words(), &pattern());
+
+        // Values wider than the field are truncated
+        row.set_wdt_window(0xff);
+        assert_eq!(row.wdt_window(), 0xf);
+        assert_changed(row.words(), 1, 0xf);
+
+        // The WDT period shares word 0 with the BOD33 and lock fields
+        let mut row = UserRow::from_words(pattern());
+        let period = row.wdt_period();
+        row.set_wdt_period(!period);
+        assert_eq!(row.wdt_period(), !period & 0xf);
+        assert_changed(row.words(), 0, 0xf << 28);
+
+        let mut row = UserRow::from_words(pattern());
+        row.set_ram_secure(0);
+        row.set_urwen(false);
+        assert_changed(row.words(), 2, 0xff << 24);
+        assert_eq!(row.words()[2] >> 24, 0);
+    }
+
+    #[test]
+    fn test_default_user_row_crc() {
+        let mut row = default_user_row();
+        assert_eq!(row.crc(), 0x45e9_e9d6);
+        assert!(row.crc_valid());
+
+        row.update_crc();
+        assert_eq!(row, default_user_row());
+    }
+
+    #[test]
+    fn test_user_row_crc() {
+        let mut row = UserRow::from_words(pattern());
+        assert!(!row.crc_valid());
+        row.update_crc();
+        assert!(row.crc_valid());
+        assert_eq!(row.crc(), crc32(&pattern()[..USER_CRC_WORD]));
+        assert_changed(row.words(), USER_CRC_WORD, 0xffff_ffff);
+
+        let enabled = row.wdt_enable();
+        row.set_wdt_enable(!enabled);
+        assert!(!row.crc_valid());
+    }
+
+    #[test]
+    fn test_boot_config_row_fields() {
+        let mut row = BootConfigRow::from_words([0xffff_ffff; ROW_WORDS]);
+        row.set_boot_nsc(0x02);
+        row.set_boot_secure(0x08);
+        row.set_bootprot(0x10);
+        row.set_bootopt(0);
+        row.set_bcren(false);
+
+        assert_eq!(row.words()[0], 0xff10_08c2);
+        assert_eq!(row.words()[1], 0xffff_fd00);
+        assert_eq!(row.words()[2..], [0xffff_ffff; ROW_WORDS - 2][..]);
+        assert_eq!(row.boot_nsc(), 0x02);
+        assert_eq!(row.boot_secure(), 0x08);
+        assert_eq!(row.bootprot(), 0x10);
+        assert_eq!(row.bootopt(), 0);
+        assert!(row.bcwen());
+        assert!(!row.bcren());
+    }
+
+    #[test]
+    fn test_boot_config_row_keys() {
+        let mut row = BootConfigRow::from_words(pattern());
+        let key = hex!("000102030405060708090a0b0c0d0e0f");
+        row.set_cekey(1, &key);
+        assert_eq!(row.cekey(1), key);
+        assert_eq!(row.words()[CEKEY_WORDS[1]], 0x0302_0100);
+        assert_eq!(row.words()[CEKEY_WORDS[1] + 3], 0x0f0e_0d0c);
+
+        row.set_crckey(&key);
+        assert_eq!(row.crckey(), key);
+
+        let bootkey = [0x5a; 32];
+        row.set_bootkey(&bootkey);
+        assert_eq!(row.bootkey(), bootkey);
+
+        // Only the key words changed
+        for (i, (&new, &old)) in row.words().iter().zip(pattern().iter()).enumerate() {
+            let key_word = (CEKEY_WORDS[1]..CEKEY_WORDS[1] + 4).contains(&i)
+                || (CRCKEY_WORD..BOOTKEY_WORD + 8).contains(&i);
+            assert!(key_word || new == old, "word {}", i);
+        }
+    }
+
+    #[test]
+    fn test_boot_config_row_hash() {
+        let mut row = BootConfigRow::from_words(pattern());
+        assert!(!row.hash_valid());
+        row.update_hash();
+        assert!(row.hash_valid());
+
+        let mut bytes = [0u8; BOCOR_HASH_WORD * 4];
+        for (chunk, word) in bytes.chunks_mut(4).zip(pattern().iter()) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        let mut sha = Sha256::new();
+        sha.update(&bytes);
+        assert_eq!(row.hash(), sha.finalize());
+        assert_eq!(row.words()[..BOCOR_HASH_WORD], pattern()[..BOCOR_HASH_WORD]);
+
+        let bootopt = row.bootopt();
+        row.set_bootopt(bootopt ^ 1);
+        assert!(!row.hash_valid());
+    }
+}
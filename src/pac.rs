@@ -0,0 +1,317 @@
+//! Peripheral Access Controller write protection and access errors
+//!
+//! Once a peripheral is configured its registers can be write-protected,
+//! optionally locked until the next reset. Writes to a protected peripheral,
+//! accesses from the wrong security world and accesses to unmapped addresses
+//! are flagged by the PAC and decoded here into `AccessError`s.
+
+use atsaml11xxx::PAC_SEC;
+use atsaml11xxx::{AC, ADC, CCL, DAC, FREQM, OPAMP, RTC, TRNG, WDT};
+use atsaml11xxx::{SERCOM0, SERCOM1, SERCOM2, TC0, TC1, TC2};
+
+/// PAC WRCTRL key clearing the write protection
+const KEY_CLR: u8 = 0x1;
+/// PAC WRCTRL key setting the write protection
+const KEY_SET: u8 = 0x2;
+/// PAC WRCTRL key setting and locking the write protection
+const KEY_SETLCK: u8 = 0x3;
+
+/// Number of AHB slaves reported in INTFLAGAHB
+const AHB_SLAVES: u32 = 7;
+
+/// Write protection errors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// The write protection of the peripheral did not change, e.g. because
+    /// it is locked
+    Protection(PeripheralId),
+}
+
+/// Peripheral identifier used by the PAC, 32 per bridge
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PeripheralId(pub u8);
+
+impl PeripheralId {
+    pub const PAC: PeripheralId = PeripheralId(0);
+    pub const PM: PeripheralId = PeripheralId(1);
+    pub const MCLK: PeripheralId = PeripheralId(2);
+    pub const RSTC: PeripheralId = PeripheralId(3);
+    pub const OSCCTRL: PeripheralId = PeripheralId(4);
+    pub const OSC32KCTRL: PeripheralId = PeripheralId(5);
+    pub const SUPC: PeripheralId = PeripheralId(6);
+    pub const GCLK: PeripheralId = PeripheralId(7);
+    pub const WDT: PeripheralId = PeripheralId(8);
+    pub const RTC: PeripheralId = PeripheralId(9);
+    pub const EIC: PeripheralId = PeripheralId(10);
+    pub const FREQM: PeripheralId = PeripheralId(11);
+    pub const PORT: PeripheralId = PeripheralId(12);
+    pub const AC: PeripheralId = PeripheralId(13);
+    pub const IDAU: PeripheralId = PeripheralId(32);
+    pub const DSU: PeripheralId = PeripheralId(33);
+    pub const NVMCTRL: PeripheralId = PeripheralId(34);
+    pub const DMAC: PeripheralId = PeripheralId(35);
+    pub const EVSYS: PeripheralId = PeripheralId(64);
+    pub const SERCOM0: PeripheralId = PeripheralId(65);
+    pub const SERCOM1: PeripheralId = PeripheralId(66);
+    pub const SERCOM2: PeripheralId = PeripheralId(67);
+    pub const TC0: PeripheralId = PeripheralId(68);
+    pub const TC1: PeripheralId = PeripheralId(69);
+    pub const TC2: PeripheralId = PeripheralId(70);
+    pub const ADC: PeripheralId = PeripheralId(71);
+    pub const DAC: PeripheralId = PeripheralId(72);
+    pub const PTC: PeripheralId = PeripheralId(73);
+    pub const TRNG: PeripheralId = PeripheralId(74);
+    pub const CCL: PeripheralId = PeripheralId(75);
+    pub const OPAMP: PeripheralId = PeripheralId(76);
+    pub const TRAM: PeripheralId = PeripheralId(77);
+
+    /// Index of the bridge, 0 to 2 for A to C, i.e. of the per bridge
+    /// register holding the peripheral's bit
+    pub fn bridge(self) -> usize {
+        (self.0 >> 5) as usize
+    }
+
+    /// Bit of the peripheral in the per bridge registers
+    pub fn mask(self) -> u32 {
+        1 << (self.0 & 0x1f)
+    }
+}
+
+/// Peripheral singletons that can be handed to the non-secure world
+pub trait Peripheral {
+    const ID: PeripheralId;
+}
+
+macro_rules! peripherals {
+    ($($Type:ident,)+) => {
+        $(
+            impl Peripheral for $Type {
+                const ID: PeripheralId = PeripheralId::$Type;
+            }
+        )+
+    };
+}
+
+peripherals! {
+    WDT,
+    RTC,
+    FREQM,
+    AC,
+    SERCOM0,
+    SERCOM1,
+    SERCOM2,
+    TC0,
+    TC1,
+    TC2,
+    ADC,
+    DAC,
+    TRNG,
+    CCL,
+    OPAMP,
+}
+
+/// Whether the registers of the peripheral are write-protected
+pub fn is_write_protected(pac: &PAC_SEC, id: PeripheralId) -> bool {
+    let status = match id.bridge() {
+        0 => pac.statusa.read().bits(),
+        1 => pac.statusb.read().bits(),
+        _ => pac.statusc.read().bits(),
+    };
+    status & id.mask() != 0
+}
+
+fn set_protection(pac: &mut PAC_SEC, id: PeripheralId, key: u8) -> Result<(), Error> {
+    let protect = key != KEY_CLR;
+    // Setting the protection twice, or clearing it when it is not set, is
+    // flagged as an access error
+    if is_write_protected(pac, id) != protect {
+        pac.wrctrl
+            .write(|w| unsafe { w.perid().bits(id.0 as u16).key().bits(key) });
+    }
+
+    if is_write_protected(pac, id) != protect {
+        return Err(Error::Protection(id));
+    }
+    Ok(())
+}
+
+/// Write-protect the registers of a peripheral
+pub fn write_protect(pac: &mut PAC_SEC, id: PeripheralId) -> Result<(), Error> {
+    set_protection(pac, id, KEY_SET)
+}
+
+/// Write-protect the registers of a peripheral until the next reset
+///
+/// The lock state cannot be read, so the peripheral must be unprotected
+/// first, e.g. with `unlock`. One that is already protected, locked or not,
+/// fails with `Error::Protection` without touching the PAC.
+pub fn write_protect_and_lock(pac: &mut PAC_SEC, id: PeripheralId) -> Result<(), Error> {
+    if is_write_protected(pac, id) {
+        return Err(Error::Protection(id));
+    }
+    set_protection(pac, id, KEY_SETLCK)
+}
+
+/// Remove the write protection of a peripheral, fails if it is locked
+pub fn unlock(pac: &mut PAC_SEC, id: PeripheralId) -> Result<(), Error> {
+    set_protection(pac, id, KEY_CLR)
+}
+
+/// Enable the PAC interrupt on access errors
+pub fn enable_error_interrupt(pac: &mut PAC_SEC) {
+    pac.intenset.write(|w| w.err().set_bit());
+}
+
+/// Disable the PAC interrupt on access errors
+pub fn disable_error_interrupt(pac: &mut PAC_SEC) {
+    pac.intenclr.write(|w| w.err().set_bit());
+}
+
+/// AHB slaves whose access errors are flagged in INTFLAGAHB
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AhbSlave {
+    Flash,
+    Hpb0,
+    Hpb1,
+    Hpb2,
+    HsramCpu,
+    HsramDmac,
+    HsramDsu,
+}
+
+impl AhbSlave {
+    fn from_bit(bit: u32) -> Self {
+        match bit {
+            0 => AhbSlave::Flash,
+            1 => AhbSlave::Hpb0,
+            2 => AhbSlave::Hpb1,
+            3 => AhbSlave::Hpb2,
+            4 => AhbSlave::HsramCpu,
+            5 => AhbSlave::HsramDmac,
+            _ => AhbSlave::HsramDsu,
+        }
+    }
+}
+
+/// An illegal access flagged by the PAC
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AccessError {
+    /// Access to an AHB slave
+    Ahb(AhbSlave),
+    /// Access to a peripheral, e.g. a write while it is protected or an
+    /// access from the wrong security world
+    Peripheral(PeripheralId),
+}
+
+/// Access errors flagged in INTFLAGAHB and INTFLAGA/B/C, iterates over the
+/// individual `AccessError`s
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AccessErrors {
+    ahb: u32,
+    peripherals: [u32; 3],
+}
+
+impl AccessErrors {
+    /// Whether no access error is flagged
+    pub fn is_empty(&self) -> bool {
+        self.ahb == 0 && self.peripherals.iter().all(|&flags| flags == 0)
+    }
+}
+
+impl Iterator for AccessErrors {
+    type Item = AccessError;
+
+    fn next(&mut self) -> Option<AccessError> {
+        if self.ahb != 0 {
+            let bit = self.ahb.trailing_zeros();
+            self.ahb &= !(1 << bit);
+            return Some(AccessError::Ahb(AhbSlave::from_bit(bit)));
+        }
+        for (bridge, flags) in self.peripherals.iter_mut().enumerate() {
+            if *flags != 0 {
+                let bit = flags.trailing_zeros();
+                *flags &= !(1 << bit);
+                return Some(AccessError::Peripheral(PeripheralId(
+                    (bridge as u32 * 32 + bit) as u8,
+                )));
+            }
+        }
+        None
+    }
+}
+
+/// Read and clear the flagged access errors, call from the PAC interrupt
+/// handler or a fault handler
+pub fn take_errors(pac: &PAC_SEC) -> AccessErrors {
+    let errors = AccessErrors {
+        ahb: pac.intflagahb.read().bits() & ((1 << AHB_SLAVES) - 1),
+        peripherals: [
+            pac.intflaga.read().bits(),
+            pac.intflagb.read().bits(),
+            pac.intflagc.read().bits(),
+        ],
+    };
+
+    // Flags are cleared by writing ones
+    pac.intflagahb.write(|w| unsafe { w.bits(errors.ahb) });
+    pac.intflaga
+        .write(|w| unsafe { w.bits(errors.peripherals[0]) });
+    pac.intflagb
+        .write(|w| unsafe { w.bits(errors.peripherals[1]) });
+    pac.intflagc
+        .write(|w| unsafe { w.bits(errors.peripherals[2]) });
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peripheral_id() {
+        assert_eq!(WDT::ID.bridge(), 0);
+        assert_eq!(WDT::ID.mask(), 1 << 8);
+        assert_eq!(PeripheralId::NVMCTRL.bridge(), 1);
+        assert_eq!(PeripheralId::NVMCTRL.mask(), 1 << 2);
+        assert_eq!(SERCOM0::ID.bridge(), 2);
+        assert_eq!(SERCOM0::ID.mask(), 1 << 1);
+        assert_eq!(OPAMP::ID.mask(), 1 << 12);
+    }
+
+    #[test]
+    fn test_access_errors() {
+        let errors = AccessErrors {
+            ahb: 0,
+            peripherals: [0, 0, 0],
+        };
+        assert!(errors.is_empty());
+        assert_eq!(errors.count(), 0);
+
+        let mut errors = AccessErrors {
+            ahb: (1 << 0) | (1 << 6),
+            peripherals: [1 << 12, 1 << 2, (1 << 1) | (1 << 13)],
+        };
+        assert!(!errors.is_empty());
+        assert_eq!(errors.next(), Some(AccessError::Ahb(AhbSlave::Flash)));
+        assert_eq!(errors.next(), Some(AccessError::Ahb(AhbSlave::HsramDsu)));
+        assert_eq!(
+            errors.next(),
+            Some(AccessError::Peripheral(PeripheralId::PORT))
+        );
+        assert_eq!(
+            errors.next(),
+            Some(AccessError::Peripheral(PeripheralId::NVMCTRL))
+        );
+        assert_eq!(
+            errors.next(),
+            Some(AccessError::Peripheral(PeripheralId::SERCOM0))
+        );
+        assert_eq!(
+            errors.next(),
+            Some(AccessError::Peripheral(PeripheralId::TRAM))
+        );
+        assert_eq!(errors.next(), None);
+        assert!(errors.is_empty());
+    }
+}
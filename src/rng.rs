@@ -1,29 +1,254 @@
-use core::iter;
+#[cfg(feature = "getrandom")]
+use core::cell::RefCell;
+use core::num::NonZeroU32;
+use core::ptr;
+use core::sync::atomic::{self, Ordering};
 
-use arrayvec::ArrayVec;
 use atsaml11xxx::{MCLK, TRNG};
+#[cfg(feature = "getrandom")]
+use cortex_m::interrupt::{self, Mutex};
+#[cfg(feature = "getrandom")]
+use getrandom;
 use hal::blocking::rng::Read;
+use nb;
 
 #[cfg(feature = "rand_core")]
-use rand_core::{impls, Error as RandError, RngCore, CryptoRng};
+use rand_core::{impls, Error as RandError, ErrorKind as RandErrorKind, RngCore, CryptoRng};
+
+/// Start of the custom error code range shared by `getrandom` and rand_core 0.5 and 0.6
+const CUSTOM_ERROR_START: u32 = (1 << 31) + (1 << 30);
 
 /// System random number generator `TRNG` as a random number provider
+///
+/// Every word read from the TRNG goes through the continuous health tests of
+/// NIST SP 800-90B. Once a test fails, all further reads fail until
+/// `reset_health_tests` is called.
 pub struct Rng {
     trng: TRNG,
+    health: HealthTests,
+}
+
+/// Continuous health test failures and invalid requests
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// The same word was read `repetition_cutoff` times in a row
+    RepetitionCount,
+    /// A word occurred `adaptive_cutoff` times within one adaptive
+    /// proportion window
+    AdaptiveProportion,
+    /// A non-blocking read asked for more than `ENTROPY_POOL_BYTES`
+    RequestTooLarge,
+}
+
+impl Error {
+    /// Error code in the custom range of `getrandom` and rand_core 0.5 and 0.6
+    pub fn code(self) -> NonZeroU32 {
+        let offset = match self {
+            Error::RepetitionCount => 1,
+            Error::AdaptiveProportion => 2,
+            Error::RequestTooLarge => 3,
+        };
+        NonZeroU32::new(CUSTOM_ERROR_START + offset).unwrap()
+    }
+}
+
+/// Cutoffs for the SP 800-90B continuous health tests, on 32 bit words
+///
+/// The defaults give a false positive rate of about 2^-20, assuming at least
+/// 8 bits of min-entropy per word. Lower cutoffs detect failures sooner at
+/// the cost of more false positives.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HealthConfig {
+    /// Fail when the same word is read this many times in a row
+    pub repetition_cutoff: u32,
+    /// Number of words in an adaptive proportion window
+    pub adaptive_window: u32,
+    /// Fail when the first word of a window occurs this many times within it
+    pub adaptive_cutoff: u32,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig {
+            repetition_cutoff: 4,
+            adaptive_window: 512,
+            adaptive_cutoff: 13,
+        }
+    }
+}
+
+/// State of the repetition count and adaptive proportion tests
+struct HealthTests {
+    config: HealthConfig,
+    failure: Option<Error>,
+    last_word: Option<u32>,
+    repetitions: u32,
+    window_word: u32,
+    window_count: u32,
+    window_len: u32,
+}
+
+impl HealthTests {
+    fn new(config: HealthConfig) -> Self {
+        HealthTests {
+            config,
+            failure: None,
+            last_word: None,
+            repetitions: 0,
+            window_word: 0,
+            window_count: 0,
+            window_len: 0,
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = HealthTests::new(self.config);
+    }
+
+    /// Run both tests on the next word, the failure is latched
+    fn check(&mut self, word: u32) -> Result<u32, Error> {
+        if let Some(err) = self.failure {
+            return Err(err);
+        }
+
+        if self.last_word == Some(word) {
+            self.repetitions += 1;
+            if self.repetitions >= self.config.repetition_cutoff {
+                self.failure = Some(Error::RepetitionCount);
+            }
+        } else {
+            self.last_word = Some(word);
+            self.repetitions = 1;
+        }
+
+        if self.window_len == 0 {
+            self.window_word = word;
+            self.window_count = 1;
+        } else if word == self.window_word {
+            self.window_count += 1;
+            if self.window_count >= self.config.adaptive_cutoff {
+                self.failure = Some(Error::AdaptiveProportion);
+            }
+        }
+        self.window_len += 1;
+        if self.window_len >= self.config.adaptive_window {
+            self.window_len = 0;
+        }
+
+        match self.failure {
+            Some(err) => Err(err),
+            None => Ok(word),
+        }
+    }
+}
+
+/// Source of raw TRNG words, so the read path can be tested against a mock
+trait WordSource {
+    fn read_word(&mut self) -> u32;
 }
 
-#[derive(Debug)]
-pub enum Error {}
+impl WordSource for TRNG {
+    fn read_word(&mut self) -> u32 {
+        while self.intflag.read().bits() == 0 {};
+        // A new random value has been generated
+        self.data.read().bits()
+    }
+}
+
+/// Fill `words` with health tested words from `source`
+fn fill_u32_checked<S: WordSource>(
+    source: &mut S,
+    health: &mut HealthTests,
+    words: &mut [u32],
+) -> Result<(), Error> {
+    for word in words.iter_mut() {
+        *word = health.check(source.read_word())?;
+    }
+
+    Ok(())
+}
+
+/// Fill `buffer` with health tested words from `source` in native byte
+/// order, the bytes of the last word that do not fit are discarded
+fn read_checked<S: WordSource>(
+    source: &mut S,
+    health: &mut HealthTests,
+    buffer: &mut [u8],
+) -> Result<(), Error> {
+    let mut chunks = buffer.chunks_exact_mut(4);
+    for chunk in &mut chunks {
+        chunk.copy_from_slice(&health.check(source.read_word())?.to_ne_bytes());
+    }
+
+    let tail = chunks.into_remainder();
+    if !tail.is_empty() {
+        let data = health.check(source.read_word())?.to_ne_bytes();
+        tail.copy_from_slice(&data[..tail.len()]);
+    }
+
+    Ok(())
+}
 
 impl Rng {
     pub fn new(trng: TRNG, mclk: &mut MCLK) -> Self {
+        Self::with_health_config(trng, mclk, HealthConfig::default())
+    }
+
+    /// Like `new` but with custom health test cutoffs
+    pub fn with_health_config(trng: TRNG, mclk: &mut MCLK, config: HealthConfig) -> Self {
         // Enable the clock
         mclk.apbcmask.write(|w| w.trng_().set_bit());
 
         // Enable random number generation
         trng.ctrla.write(|w| w.enable().set_bit());
 
-        Rng { trng }
+        Rng {
+            trng,
+            health: HealthTests::new(config),
+        }
+    }
+
+    /// Read a single health tested word
+    pub fn next_u32(&mut self) -> Result<u32, Error> {
+        self.health.check(self.trng.read_word())
+    }
+
+    /// Fill `words` with health tested words, the same values `read` would
+    /// return as bytes
+    pub fn fill_u32(&mut self, words: &mut [u32]) -> Result<(), Error> {
+        fill_u32_checked(&mut self.trng, &mut self.health, words)
+    }
+
+    /// Clear a latched health test failure and restart the tests
+    pub fn reset_health_tests(&mut self) {
+        self.health.reset();
+    }
+
+    /// Keep the TRNG running in standby sleep, so it can keep generating
+    /// entropy and wake the CPU from `InterruptRng`
+    pub fn set_run_in_standby(&mut self, enable: bool) {
+        self.trng.ctrla.modify(|_, w| w.runstdby().bit(enable));
+    }
+
+    /// Emit the TRNG data ready event, which can be routed through EVSYS to
+    /// trigger other peripherals
+    pub fn enable_event_output(&mut self) {
+        self.trng.evctrl.write(|w| w.datardyeo().set_bit());
+    }
+
+    pub fn disable_event_output(&mut self) {
+        self.trng.evctrl.write(|w| w.datardyeo().clear_bit());
+    }
+
+    /// Switch to filling an entropy pool from the DATARDY interrupt
+    pub fn into_interrupt_driven(self) -> InterruptRng {
+        self.trng.intenset.write(|w| w.datardy().set_bit());
+
+        InterruptRng {
+            trng: self.trng,
+            health: self.health,
+            pool: EntropyPool::new(),
+        }
     }
 
     pub fn free(self) -> TRNG {
@@ -35,40 +260,529 @@ impl Read for Rng {
     type Error = Error;
 
     fn read(&mut self, buffer: &mut [u8]) -> Result<(), Self::Error> {
-        let data = iter::repeat_with(|| {
-            while self.trng.intflag.read().bits() == 0 {};
-            // A new random value has been generated
-            self.trng.data.read().bits()
-        }).flat_map(|data| ArrayVec::from(data.to_ne_bytes()));
-
-        for (in_, next) in &mut buffer.into_iter().zip(data) {
-            // Write number into provided buffer
-            *in_ = next;
-        }
-
-        Ok(())
+        read_checked(&mut self.trng, &mut self.health, buffer)
     }
 }
 
 #[cfg(feature = "rand_core")]
 impl RngCore for Rng {
     fn fill_bytes(&mut self, dest: &mut [u8]) {
-        self.read(dest).unwrap();
+        self.try_fill_bytes(dest).unwrap();
     }
 
     fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), RandError> {
-        self.fill_bytes(dest);
-        Ok(())
+        self.read(dest).map_err(|err| match err {
+            Error::RepetitionCount => {
+                RandError::new(RandErrorKind::Unavailable, "TRNG repetition count test failed")
+            }
+            Error::AdaptiveProportion => {
+                RandError::new(RandErrorKind::Unavailable, "TRNG adaptive proportion test failed")
+            }
+            Error::RequestTooLarge => {
+                RandError::new(RandErrorKind::Unexpected, "TRNG request too large")
+            }
+        })
     }
 
     fn next_u32(&mut self) -> u32 {
-        impls::next_u32_via_fill(self)
+        Rng::next_u32(self).unwrap()
     }
 
     fn next_u64(&mut self) -> u64 {
-        impls::next_u64_via_fill(self)
+        impls::next_u64_via_u32(self)
     }
 }
 
 #[cfg(feature = "rand_core")]
 impl CryptoRng for Rng {}
+
+/// `RngCore` and `CryptoRng` for the rand_core versions with numeric error codes
+#[cfg(any(feature = "rand_core_05", feature = "rand_core_06"))]
+macro_rules! rand_core_impls {
+    ($rand_core:ident) => {
+        impl ::$rand_core::RngCore for Rng {
+            fn next_u32(&mut self) -> u32 {
+                Rng::next_u32(self).unwrap()
+            }
+
+            fn next_u64(&mut self) -> u64 {
+                ::$rand_core::impls::next_u64_via_u32(self)
+            }
+
+            fn fill_bytes(&mut self, dest: &mut [u8]) {
+                self.read(dest).unwrap();
+            }
+
+            fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), ::$rand_core::Error> {
+                self.read(dest).map_err(|err| ::$rand_core::Error::from(err.code()))
+            }
+        }
+
+        impl ::$rand_core::CryptoRng for Rng {}
+    };
+}
+
+#[cfg(feature = "rand_core_05")]
+rand_core_impls!(rand_core_05);
+#[cfg(feature = "rand_core_06")]
+rand_core_impls!(rand_core_06);
+
+#[cfg(feature = "getrandom")]
+static GETRANDOM_RNG: Mutex<RefCell<Option<Rng>>> = Mutex::new(RefCell::new(None));
+
+/// Error code of `getrandom_trng` when no `Rng` has been installed
+#[cfg(feature = "getrandom")]
+pub const GETRANDOM_NO_RNG: u32 = CUSTOM_ERROR_START;
+
+/// Install the `Rng` used by the `getrandom` backend, returning the previous one
+#[cfg(feature = "getrandom")]
+pub fn set_getrandom_rng(rng: Rng) -> Option<Rng> {
+    interrupt::free(|cs| GETRANDOM_RNG.borrow(cs).replace(Some(rng)))
+}
+
+/// Remove the `Rng` used by the `getrandom` backend
+#[cfg(feature = "getrandom")]
+pub fn take_getrandom_rng() -> Option<Rng> {
+    interrupt::free(|cs| GETRANDOM_RNG.borrow(cs).borrow_mut().take())
+}
+
+/// `getrandom` custom backend reading from the `Rng` installed with
+/// `set_getrandom_rng`, register it with `register_getrandom_trng!`
+///
+/// The TRNG is read with interrupts disabled.
+#[cfg(feature = "getrandom")]
+pub fn getrandom_trng(dest: &mut [u8]) -> Result<(), getrandom::Error> {
+    interrupt::free(|cs| match GETRANDOM_RNG.borrow(cs).borrow_mut().as_mut() {
+        Some(rng) => rng.read(dest).map_err(|err| getrandom::Error::from(err.code())),
+        None => Err(getrandom::Error::from(NonZeroU32::new(GETRANDOM_NO_RNG).unwrap())),
+    })
+}
+
+/// Register `rng::getrandom_trng` as the custom `getrandom` backend, invoke
+/// once in the application crate
+#[cfg(feature = "getrandom")]
+#[macro_export]
+macro_rules! register_getrandom_trng {
+    () => {
+        $crate::getrandom::register_custom_getrandom!($crate::rng::getrandom_trng);
+    };
+}
+
+/// Number of words held by the entropy pool of `InterruptRng`
+pub const ENTROPY_POOL_WORDS: usize = 32;
+/// Number of bytes held by the entropy pool of `InterruptRng`
+pub const ENTROPY_POOL_BYTES: usize = ENTROPY_POOL_WORDS * 4;
+
+/// Ring buffer of health tested TRNG words
+struct EntropyPool {
+    words: [u32; ENTROPY_POOL_WORDS],
+    start: usize,
+    len: usize,
+}
+
+impl EntropyPool {
+    fn new() -> Self {
+        EntropyPool {
+            words: [0; ENTROPY_POOL_WORDS],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.len == ENTROPY_POOL_WORDS
+    }
+
+    /// Add a word, dropping it if the pool is already full
+    fn push(&mut self, word: u32) {
+        if !self.is_full() {
+            self.words[(self.start + self.len) % ENTROPY_POOL_WORDS] = word;
+            self.len += 1;
+        }
+    }
+
+    /// Fill all of `buffer` and return true, or leave the pool untouched if
+    /// it holds too few words. Consumed words are wiped.
+    fn take(&mut self, buffer: &mut [u8]) -> bool {
+        if buffer.len() > self.len * 4 {
+            return false;
+        }
+
+        for chunk in buffer.chunks_mut(4) {
+            let word = &mut self.words[self.start];
+            chunk.copy_from_slice(&word.to_ne_bytes()[..chunk.len()]);
+            unsafe { ptr::write_volatile(word, 0) };
+
+            self.start = (self.start + 1) % ENTROPY_POOL_WORDS;
+            self.len -= 1;
+        }
+        atomic::compiler_fence(Ordering::SeqCst);
+
+        true
+    }
+
+    fn clear(&mut self) {
+        for word in self.words.iter_mut() {
+            unsafe { ptr::write_volatile(word, 0) };
+        }
+        atomic::compiler_fence(Ordering::SeqCst);
+
+        self.start = 0;
+        self.len = 0;
+    }
+}
+
+impl Drop for EntropyPool {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// Health test a word from the interrupt handler and add it to the pool,
+/// returns whether the interrupt should stay enabled
+fn pool_word(health: &mut HealthTests, pool: &mut EntropyPool, word: u32) -> bool {
+    match health.check(word) {
+        Ok(word) => {
+            pool.push(word);
+            !pool.is_full()
+        }
+        Err(_) => {
+            pool.clear();
+            false
+        }
+    }
+}
+
+/// `TRNG` filling an entropy pool from its DATARDY interrupt
+///
+/// `on_interrupt` has to be called from the `TRNG` interrupt handler, which
+/// also has to be unmasked in the NVIC. The interrupt is disabled while the
+/// pool is full and enabled again once entropy is taken out with `try_read`.
+/// Together with `Rng::set_run_in_standby` the pool keeps filling while the
+/// CPU sleeps.
+///
+/// `on_interrupt` and `try_read` both take `&mut self`, so an instance shared
+/// by the handler and thread code lives in a `Mutex<RefCell<..>>`, and every
+/// `try_read` runs inside `interrupt::free` so the handler cannot preempt it:
+/// ```ignore
+/// static RNG: Mutex<RefCell<Option<InterruptRng>>> = Mutex::new(RefCell::new(None));
+///
+/// let rng = Rng::new(p.TRNG, &mut p.MCLK).into_interrupt_driven();
+/// interrupt::free(|cs| RNG.borrow(cs).replace(Some(rng)));
+/// unsafe { NVIC::unmask(Interrupt::TRNG) };
+///
+/// let mut key = [0u8; 16];
+/// let result = loop {
+///     let result = interrupt::free(|cs| match RNG.borrow(cs).borrow_mut().as_mut() {
+///         Some(rng) => rng.try_read(&mut key),
+///         None => Err(nb::Error::WouldBlock),
+///     });
+///     match result {
+///         Err(nb::Error::WouldBlock) => asm::wfi(),
+///         result => break result,
+///     }
+/// };
+///
+/// #[interrupt]
+/// fn TRNG() {
+///     interrupt::free(|cs| {
+///         if let Some(rng) = RNG.borrow(cs).borrow_mut().as_mut() {
+///             rng.on_interrupt();
+///         }
+///     });
+/// }
+/// ```
+pub struct InterruptRng {
+    trng: TRNG,
+    health: HealthTests,
+    pool: EntropyPool,
+}
+
+impl InterruptRng {
+    /// Move the next word into the pool, call from the `TRNG` interrupt handler
+    pub fn on_interrupt(&mut self) {
+        if self.trng.intflag.read().datardy().bit_is_clear() {
+            return;
+        }
+
+        // Reading the data clears the interrupt flag
+        let word = self.trng.data.read().bits();
+        if !pool_word(&mut self.health, &mut self.pool, word) {
+            self.trng.intenclr.write(|w| w.datardy().set_bit());
+        }
+    }
+
+    /// Number of bytes available to `try_read` right now
+    pub fn available(&self) -> usize {
+        self.pool.len * 4
+    }
+
+    /// Fill all of `buffer` from the entropy pool without blocking
+    ///
+    /// Returns `WouldBlock` until the pool holds enough entropy, and the
+    /// latched error once a health test failed. A `buffer` longer than
+    /// `ENTROPY_POOL_BYTES` can never be filled and fails with
+    /// `Error::RequestTooLarge`.
+    pub fn try_read(&mut self, buffer: &mut [u8]) -> nb::Result<(), Error> {
+        if buffer.len() > ENTROPY_POOL_BYTES {
+            return Err(nb::Error::Other(Error::RequestTooLarge));
+        }
+
+        if let Some(err) = self.health.failure {
+            return Err(nb::Error::Other(err));
+        }
+
+        if !self.pool.take(buffer) {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        // Refill the pool
+        self.trng.intenset.write(|w| w.datardy().set_bit());
+        Ok(())
+    }
+
+    /// Clear a latched health test failure and start refilling the pool
+    pub fn reset_health_tests(&mut self) {
+        self.health.reset();
+        self.trng.intenset.write(|w| w.datardy().set_bit());
+    }
+
+    /// Disable the interrupt and go back to blocking reads, the pool is wiped
+    pub fn into_blocking(self) -> Rng {
+        self.trng.intenclr.write(|w| w.datardy().set_bit());
+
+        Rng {
+            trng: self.trng,
+            health: self.health,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Host side stand in for the TRNG, replaying generated words
+    struct MockTrng<F: FnMut(u32) -> u32> {
+        next: F,
+        count: u32,
+    }
+
+    impl<F: FnMut(u32) -> u32> MockTrng<F> {
+        fn new(next: F) -> Self {
+            MockTrng { next, count: 0 }
+        }
+    }
+
+    impl<F: FnMut(u32) -> u32> WordSource for MockTrng<F> {
+        fn read_word(&mut self) -> u32 {
+            let word = (self.next)(self.count);
+            self.count += 1;
+            word
+        }
+    }
+
+    fn xorshift(state: &mut u32) -> u32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state
+    }
+
+    #[test]
+    fn test_health_tests_pass() {
+        let mut state = 0x1234_5678;
+        let mut trng = MockTrng::new(|_| xorshift(&mut state));
+        let mut health = HealthTests::new(HealthConfig::default());
+
+        let mut buffer = [0u8; 4096];
+        for _ in 0..16 {
+            assert_eq!(read_checked(&mut trng, &mut health, &mut buffer), Ok(()));
+        }
+        assert_eq!(trng.count, 16 * 1024);
+    }
+
+    #[test]
+    fn test_health_tests_stuck() {
+        let mut trng = MockTrng::new(|_| 0xdead_beef);
+        let mut health = HealthTests::new(HealthConfig::default());
+
+        let mut buffer = [0u8; 64];
+        assert_eq!(
+            read_checked(&mut trng, &mut health, &mut buffer),
+            Err(Error::RepetitionCount)
+        );
+        assert_eq!(trng.count, 4);
+
+        // The failure is latched even once the source recovers
+        let mut state = 0x1234_5678;
+        let mut trng = MockTrng::new(|_| xorshift(&mut state));
+        assert_eq!(
+            read_checked(&mut trng, &mut health, &mut buffer),
+            Err(Error::RepetitionCount)
+        );
+
+        health.reset();
+        assert_eq!(read_checked(&mut trng, &mut health, &mut buffer), Ok(()));
+    }
+
+    #[test]
+    fn test_health_tests_biased() {
+        // Every other word is the same value, which never repeats back to back
+        let mut state = 0x1234_5678;
+        let mut trng = MockTrng::new(|i| if i % 2 == 0 { 0x5a5a_5a5a } else { xorshift(&mut state) });
+        let mut health = HealthTests::new(HealthConfig::default());
+
+        let mut buffer = [0u8; 4096];
+        assert_eq!(
+            read_checked(&mut trng, &mut health, &mut buffer),
+            Err(Error::AdaptiveProportion)
+        );
+        assert_eq!(trng.count, 25);
+    }
+
+    #[test]
+    fn test_health_tests_custom_cutoffs() {
+        let config = HealthConfig {
+            repetition_cutoff: 8,
+            adaptive_window: 64,
+            adaptive_cutoff: 40,
+        };
+
+        // Seven repetitions fail the default cutoff but not the custom one
+        let mut buffer = [0u8; 256];
+        let mut trng = MockTrng::new(|i| if i < 7 { 1 } else { i });
+        let mut health = HealthTests::new(HealthConfig::default());
+        assert_eq!(
+            read_checked(&mut trng, &mut health, &mut buffer),
+            Err(Error::RepetitionCount)
+        );
+
+        let mut trng = MockTrng::new(|i| if i < 7 { 1 } else { i });
+        let mut health = HealthTests::new(config);
+        assert_eq!(read_checked(&mut trng, &mut health, &mut buffer), Ok(()));
+
+        let mut trng = MockTrng::new(|i| if i % 2 == 0 { 1 } else { i });
+        let mut health = HealthTests::new(config);
+        assert_eq!(read_checked(&mut trng, &mut health, &mut buffer), Ok(()));
+
+        let mut trng = MockTrng::new(|i| if i % 4 == 3 { i } else { 1 });
+        let mut health = HealthTests::new(config);
+        assert_eq!(
+            read_checked(&mut trng, &mut health, &mut buffer),
+            Err(Error::AdaptiveProportion)
+        );
+    }
+
+    #[test]
+    fn test_read_words_in_order() {
+        let mut trng = MockTrng::new(|i| 0x0101_0101 * (i + 1));
+        let mut health = HealthTests::new(HealthConfig::default());
+
+        // Every read starts with a fresh word, a partial last word keeps its
+        // first bytes
+        let mut expected = 1u8;
+        for len in 0..=13 {
+            let mut buffer = [0u8; 13];
+            read_checked(&mut trng, &mut health, &mut buffer[..len]).unwrap();
+            for chunk in buffer[..len].chunks(4) {
+                for b in chunk {
+                    assert_eq!(*b, expected);
+                }
+                expected += 1;
+            }
+            assert!(buffer[len..].iter().all(|b| *b == 0));
+        }
+        assert_eq!(trng.count, expected as u32 - 1);
+
+        let mut words = [0u32; 5];
+        fill_u32_checked(&mut trng, &mut health, &mut words).unwrap();
+        for (i, word) in words.iter().enumerate() {
+            assert_eq!(*word, 0x0101_0101 * (expected as u32 + i as u32));
+        }
+
+        // Bytes and words of the same stream agree
+        let mut trng_bytes = MockTrng::new(|i| i.wrapping_mul(0x9e37_79b9));
+        let mut trng_words = MockTrng::new(|i| i.wrapping_mul(0x9e37_79b9));
+        let mut health_bytes = HealthTests::new(HealthConfig::default());
+        let mut bytes = [0u8; 64];
+        let mut words = [0u32; 16];
+        read_checked(&mut trng_bytes, &mut health_bytes, &mut bytes).unwrap();
+        fill_u32_checked(&mut trng_words, &mut health, &mut words).unwrap();
+        for (chunk, word) in bytes.chunks(4).zip(words.iter()) {
+            assert_eq!(chunk, word.to_ne_bytes());
+        }
+    }
+
+    #[test]
+    fn test_fill_u32_health_failure() {
+        let mut trng = MockTrng::new(|i| if i < 2 { i } else { 9 });
+        let mut health = HealthTests::new(HealthConfig::default());
+
+        let mut words = [0u32; 8];
+        assert_eq!(
+            fill_u32_checked(&mut trng, &mut health, &mut words),
+            Err(Error::RepetitionCount)
+        );
+        assert_eq!(words[..5], [0, 1, 9, 9, 9]);
+        assert_eq!(trng.count, 6);
+    }
+
+    #[test]
+    fn test_error_codes() {
+        assert_eq!(Error::RepetitionCount.code().get(), 0xc000_0001);
+        assert_eq!(Error::AdaptiveProportion.code().get(), 0xc000_0002);
+        assert_eq!(Error::RequestTooLarge.code().get(), 0xc000_0003);
+    }
+
+    #[test]
+    fn test_entropy_pool() {
+        let mut trng = MockTrng::new(|i| i + 1);
+        let mut health = HealthTests::new(HealthConfig::default());
+        let mut pool = EntropyPool::new();
+
+        let mut buffer = [0u8; 12];
+        assert!(!pool.take(&mut buffer));
+
+        for _ in 0..ENTROPY_POOL_WORDS - 1 {
+            assert!(pool_word(&mut health, &mut pool, trng.read_word()));
+        }
+        assert!(!pool_word(&mut health, &mut pool, trng.read_word()));
+        assert!(pool.is_full());
+
+        // A full pool drops new words
+        pool.push(0xffff_ffff);
+        assert_eq!(pool.len, ENTROPY_POOL_WORDS);
+
+        // Reads are all or nothing and partial words are consumed whole
+        let mut large = [0u8; ENTROPY_POOL_BYTES];
+        assert!(pool.take(&mut buffer[..6]));
+        assert_eq!(buffer[..6], [1, 0, 0, 0, 2, 0]);
+        assert_eq!(pool.len, ENTROPY_POOL_WORDS - 2);
+        assert!(!pool.take(&mut large));
+
+        // Refill across the end of the ring
+        assert!(pool_word(&mut health, &mut pool, trng.read_word()));
+        assert!(!pool_word(&mut health, &mut pool, trng.read_word()));
+        assert!(pool.take(&mut large));
+        for (i, word) in large.chunks(4).enumerate() {
+            assert_eq!(word, (i as u32 + 3).to_ne_bytes());
+        }
+        assert_eq!(pool.words, [0; ENTROPY_POOL_WORDS]);
+        assert_eq!(pool.len, 0);
+    }
+
+    #[test]
+    fn test_entropy_pool_health_failure() {
+        let mut health = HealthTests::new(HealthConfig::default());
+        let mut pool = EntropyPool::new();
+
+        assert!(pool_word(&mut health, &mut pool, 7));
+        assert!(pool_word(&mut health, &mut pool, 7));
+        assert!(pool_word(&mut health, &mut pool, 7));
+        assert!(!pool_word(&mut health, &mut pool, 7));
+        assert_eq!(health.failure, Some(Error::RepetitionCount));
+        assert_eq!(pool.len, 0);
+        assert_eq!(pool.words, [0; ENTROPY_POOL_WORDS]);
+    }
+}
@@ -0,0 +1,227 @@
+//! Security attribution of peripherals, pins and event channels
+//!
+//! Peripherals are handed to the non-secure world through the PAC, which
+//! gives back a `NonSecure` marker holding the peripheral so the secure image
+//! can no longer use it. The mix-secure peripherals PORT, EIC and EVSYS stay
+//! secure and are instead split per pin, per external interrupt and per event
+//! channel through their own NONSEC registers.
+//!
+//! Every change is read back and checked, a change the hardware did not
+//! apply, e.g. because the attribution is locked, is reported as an error.
+
+use atsaml11xxx::{EIC_SEC, EVSYS_SEC, PAC_SEC};
+
+use gpio::{PinId, PORT};
+use pac::{Peripheral, PeripheralId};
+
+/// PAC WRCTRL key setting a peripheral secure
+const KEY_SETSEC: u8 = 0x4;
+/// PAC WRCTRL key setting a peripheral non-secure
+const KEY_SETNONSEC: u8 = 0x5;
+
+/// Attribution errors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// The peripheral did not take the requested attribution
+    Peripheral(PeripheralId),
+    /// The PORT pins in the mask did not take the requested attribution
+    Pins(u32),
+    /// The external interrupts in the mask did not take the requested
+    /// attribution
+    ExtInts(u32),
+    /// The event channels in the mask did not take the requested attribution
+    EventChannels(u32),
+}
+
+/// Marker for a peripheral or pin that has been handed to the non-secure
+/// world, keeps it out of reach of the secure image until it is reclaimed
+pub struct NonSecure<P> {
+    inner: P,
+}
+
+/// Snapshot of the PAC NONSECA/B/C registers
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attribution {
+    nonsec: [u32; 3],
+}
+
+impl Attribution {
+    /// Read back the current peripheral attribution
+    pub fn read(pac: &PAC_SEC) -> Self {
+        Attribution {
+            nonsec: [
+                pac.nonseca.read().bits(),
+                pac.nonsecb.read().bits(),
+                pac.nonsecc.read().bits(),
+            ],
+        }
+    }
+
+    /// Whether the peripheral is attributed to the non-secure world
+    pub fn is_nonsecure(&self, id: PeripheralId) -> bool {
+        self.nonsec[id.bridge()] & id.mask() != 0
+    }
+
+    /// Check that every peripheral in `nonsecure` is non-secure and every
+    /// one in `secure` is secure
+    pub fn verify(&self, nonsecure: &[PeripheralId], secure: &[PeripheralId]) -> Result<(), Error> {
+        for &id in nonsecure {
+            if !self.is_nonsecure(id) {
+                return Err(Error::Peripheral(id));
+            }
+        }
+        for &id in secure {
+            if self.is_nonsecure(id) {
+                return Err(Error::Peripheral(id));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn set_attribution(pac: &mut PAC_SEC, id: PeripheralId, nonsecure: bool) -> Result<(), Error> {
+    let key = if nonsecure { KEY_SETNONSEC } else { KEY_SETSEC };
+    pac.wrctrl
+        .write(|w| unsafe { w.perid().bits(id.0 as u16).key().bits(key) });
+
+    if Attribution::read(pac).is_nonsecure(id) != nonsecure {
+        return Err(Error::Peripheral(id));
+    }
+    Ok(())
+}
+
+/// Hand a peripheral to the non-secure world
+///
+/// On error the peripheral is dropped, as there is no telling which world
+/// owns it.
+pub fn assign_nonsecure<P: Peripheral>(
+    pac: &mut PAC_SEC,
+    peripheral: P,
+) -> Result<NonSecure<P>, Error> {
+    set_attribution(pac, P::ID, true)?;
+    Ok(NonSecure { inner: peripheral })
+}
+
+/// Take a peripheral back from the non-secure world
+pub fn reclaim<P: Peripheral>(pac: &mut PAC_SEC, peripheral: NonSecure<P>) -> Result<P, Error> {
+    set_attribution(pac, P::ID, false)?;
+    Ok(peripheral.inner)
+}
+
+/// Check the readback of a per pin or per channel NONSEC register
+fn verify_mask(readback: u32, mask: u32, nonsecure: bool) -> Result<(), u32> {
+    let expected = if nonsecure { mask } else { 0 };
+    match (readback & mask) ^ expected {
+        0 => Ok(()),
+        failed => Err(failed),
+    }
+}
+
+fn set_pins_nonsecure(mask: u32, nonsecure: bool) -> Result<(), Error> {
+    let group = unsafe { &(*PORT::ptr()).group0 };
+    group.nonsec.modify(|r, w| unsafe {
+        w.bits(if nonsecure {
+            r.bits() | mask
+        } else {
+            r.bits() & !mask
+        })
+    });
+    verify_mask(group.nonsec.read().bits(), mask, nonsecure).map_err(Error::Pins)
+}
+
+/// Hand a PORT pin to the non-secure world
+pub fn assign_pin_nonsecure<P: PinId>(pin: P) -> Result<NonSecure<P>, Error> {
+    set_pins_nonsecure(1 << P::PIN_NO, true)?;
+    Ok(NonSecure { inner: pin })
+}
+
+/// Take a PORT pin back from the non-secure world
+pub fn reclaim_pin<P: PinId>(pin: NonSecure<P>) -> Result<P, Error> {
+    set_pins_nonsecure(1 << P::PIN_NO, false)?;
+    Ok(pin.inner)
+}
+
+/// PORT pins attributed to the non-secure world, one bit per pin
+pub fn nonsecure_pins() -> u32 {
+    unsafe { (*PORT::ptr()).group0.nonsec.read().bits() }
+}
+
+/// Set the attribution of the EIC external interrupts, and the NMI in bit
+/// 31, in `mask`
+pub fn set_extints_nonsecure(eic: &mut EIC_SEC, mask: u32, nonsecure: bool) -> Result<(), Error> {
+    eic.nonsec.modify(|r, w| unsafe {
+        w.bits(if nonsecure {
+            r.bits() | mask
+        } else {
+            r.bits() & !mask
+        })
+    });
+    verify_mask(nonsecure_extints(eic), mask, nonsecure).map_err(Error::ExtInts)
+}
+
+/// EIC external interrupts attributed to the non-secure world
+pub fn nonsecure_extints(eic: &EIC_SEC) -> u32 {
+    eic.nonsec.read().bits()
+}
+
+/// Set the attribution of the EVSYS channels in `mask`
+pub fn set_channels_nonsecure(
+    evsys: &mut EVSYS_SEC,
+    mask: u32,
+    nonsecure: bool,
+) -> Result<(), Error> {
+    evsys.nonsecchan.modify(|r, w| unsafe {
+        w.bits(if nonsecure {
+            r.bits() | mask
+        } else {
+            r.bits() & !mask
+        })
+    });
+    verify_mask(nonsecure_channels(evsys), mask, nonsecure).map_err(Error::EventChannels)
+}
+
+/// EVSYS channels attributed to the non-secure world
+pub fn nonsecure_channels(evsys: &EVSYS_SEC) -> u32 {
+    evsys.nonsecchan.read().bits()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attribution() {
+        let attribution = Attribution {
+            nonsec: [1 << 9, 0, (1 << 1) | (1 << 10)],
+        };
+        assert!(attribution.is_nonsecure(PeripheralId::RTC));
+        assert!(attribution.is_nonsecure(PeripheralId::SERCOM0));
+        assert!(attribution.is_nonsecure(PeripheralId::TRNG));
+        assert!(!attribution.is_nonsecure(PeripheralId::WDT));
+        assert!(!attribution.is_nonsecure(PeripheralId::SERCOM1));
+
+        assert_eq!(
+            attribution.verify(
+                &[PeripheralId::RTC, PeripheralId::SERCOM0],
+                &[PeripheralId::WDT, PeripheralId::TC0]
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            attribution.verify(&[PeripheralId::RTC, PeripheralId::SERCOM1], &[]),
+            Err(Error::Peripheral(PeripheralId::SERCOM1))
+        );
+        assert_eq!(
+            attribution.verify(&[], &[PeripheralId::TRNG]),
+            Err(Error::Peripheral(PeripheralId::TRNG))
+        );
+    }
+
+    #[test]
+    fn test_verify_mask() {
+        assert_eq!(verify_mask(0b1111, 0b0110, true), Ok(()));
+        assert_eq!(verify_mask(0b1001, 0b0110, false), Ok(()));
+        assert_eq!(verify_mask(0b0100, 0b0110, true), Err(0b0010));
+        assert_eq!(verify_mask(0b0100, 0b0110, false), Err(0b0100));
+    }
+}
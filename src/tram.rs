@@ -0,0 +1,271 @@
+//! TrustRAM, 256 bytes of secure RAM for keys and other secrets
+//!
+//! The contents are data scrambled with a key drawn from the `Rng` and
+//! erased on tamper detection. With data remanence prevention the RAM is
+//! also cleared on reset. Secrets live in `Slot`s, fixed ranges of words
+//! handed out once, and are only accessed with volatile reads and writes.
+
+use core::ptr;
+
+use atsaml11xxx::{MCLK, TRAM};
+
+use crypto::Aes128Ref;
+use rng::{self, Rng};
+
+/// Number of 32 bit words in TrustRAM
+pub const TRAM_WORDS: usize = 64;
+
+/// Number of words of an AES-128 key
+const AES128_KEY_WORDS: usize = 4;
+
+/// Mask of the 30 bit DSCC data scrambling key
+const SCRAMBLE_KEY_MASK: u32 = 0x3fff_ffff;
+
+/// TrustRAM errors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// Drawing the scrambling key failed
+    Rng(rng::Error),
+    /// A buffer or slot does not have the length the operation needs
+    InvalidLength,
+}
+
+/// TrustRAM protections
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Config {
+    /// Clear the RAM on reset and power loss
+    pub remanence_prevention: bool,
+    /// Erase the RAM when the RTC detects a tamper event
+    pub erase_on_tamper: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            remanence_prevention: true,
+            erase_on_tamper: true,
+        }
+    }
+}
+
+/// A range of TrustRAM words allocated by `Tram::alloc`
+#[derive(Debug, PartialEq)]
+pub struct Slot {
+    offset: usize,
+    len: usize,
+}
+
+impl Slot {
+    /// Number of words in the slot
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Slot allocation and volatile access to the RAM words
+struct SecretRam {
+    base: *mut u32,
+    next: usize,
+}
+
+impl SecretRam {
+    fn alloc(&mut self, words: usize) -> Option<Slot> {
+        if words > TRAM_WORDS - self.next {
+            return None;
+        }
+        let slot = Slot {
+            offset: self.next,
+            len: words,
+        };
+        self.next += words;
+        Some(slot)
+    }
+
+    fn write(&mut self, slot: &Slot, words: &[u32]) -> Result<(), Error> {
+        if words.len() != slot.len {
+            return Err(Error::InvalidLength);
+        }
+        for (i, &word) in words.iter().enumerate() {
+            unsafe { ptr::write_volatile(self.base.add(slot.offset + i), word) };
+        }
+        Ok(())
+    }
+
+    fn read(&self, slot: &Slot, words: &mut [u32]) -> Result<(), Error> {
+        if words.len() != slot.len {
+            return Err(Error::InvalidLength);
+        }
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = unsafe { ptr::read_volatile(self.base.add(slot.offset + i)) };
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, offset: usize, len: usize) {
+        for i in offset..offset + len {
+            unsafe { ptr::write_volatile(self.base.add(i), 0) };
+        }
+    }
+
+    fn aes128_key<'a>(&'a self, slot: &Slot) -> Result<Aes128Ref<'a>, Error> {
+        if slot.len != AES128_KEY_WORDS {
+            return Err(Error::InvalidLength);
+        }
+        let key = unsafe { &*(self.base.add(slot.offset) as *const [u8; AES128_KEY_WORDS * 4]) };
+        Ok(Aes128Ref::new(key))
+    }
+}
+
+/// TrustRAM with data scrambling enabled
+pub struct Tram {
+    tram: TRAM,
+    ram: SecretRam,
+}
+
+impl Tram {
+    /// Reset TrustRAM and enable it with a fresh scrambling key from `rng`
+    pub fn new(tram: TRAM, mclk: &mut MCLK, rng: &mut Rng, config: Config) -> Result<Self, Error> {
+        mclk.apbcmask.modify(|_, w| w.tram_().set_bit());
+
+        tram.ctrla.write(|w| w.swrst().set_bit());
+        while tram.syncbusy.read().swrst().bit_is_set() {}
+
+        let mut key = rng.next_u32().map_err(Error::Rng)?;
+        tram.dscc
+            .write(|w| unsafe { w.dsckey().bits(key & SCRAMBLE_KEY_MASK).dscen().set_bit() });
+        unsafe { ptr::write_volatile(&mut key, 0) };
+
+        tram.ctrla.write(|w| {
+            w.drp()
+                .bit(config.remanence_prevention)
+                .tampers()
+                .bit(config.erase_on_tamper)
+                .enable()
+                .set_bit()
+        });
+        while tram.syncbusy.read().enable().bit_is_set() {}
+
+        let base = tram.ram.as_ptr() as *mut u32;
+        Ok(Tram {
+            tram,
+            ram: SecretRam { base, next: 0 },
+        })
+    }
+
+    /// Allocate a slot of `words` words, `None` once TrustRAM is full
+    pub fn alloc(&mut self, words: usize) -> Option<Slot> {
+        self.ram.alloc(words)
+    }
+
+    /// Store `words` in `slot`, which has to have the same length
+    pub fn write(&mut self, slot: &Slot, words: &[u32]) -> Result<(), Error> {
+        self.ram.write(slot, words)
+    }
+
+    /// Read `slot` into `words`, which has to have the same length
+    pub fn read(&self, slot: &Slot, words: &mut [u32]) -> Result<(), Error> {
+        self.ram.read(slot, words)
+    }
+
+    /// Overwrite `slot` with zeros
+    pub fn clear(&mut self, slot: &Slot) {
+        self.ram.clear(slot.offset, slot.len);
+    }
+
+    /// AES-128 using the key stored in `slot`, which the ROM reads straight
+    /// from TrustRAM
+    ///
+    /// The key is stored as the 16 key bytes in memory order, e.g. with
+    /// `write` from words built with `u32::from_ne_bytes`. The cipher modes
+    /// take it through their `with_cipher` constructors, e.g.
+    /// `Aes128Gcm::with_cipher`.
+    pub fn aes128<'a>(&'a self, slot: &Slot) -> Result<Aes128Ref<'a>, Error> {
+        self.ram.aes128_key(slot)
+    }
+
+    /// Erase all of TrustRAM, including the scrambling key, and release the
+    /// peripheral
+    pub fn erase(mut self) -> TRAM {
+        self.ram.clear(0, TRAM_WORDS);
+        self.tram.ctrla.write(|w| w.swrst().set_bit());
+        while self.tram.syncbusy.read().swrst().bit_is_set() {}
+        self.tram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alloc() {
+        let mut words = [0u32; TRAM_WORDS];
+        let mut ram = SecretRam {
+            base: words.as_mut_ptr(),
+            next: 0,
+        };
+
+        let first = ram.alloc(4).unwrap();
+        let second = ram.alloc(TRAM_WORDS - 8).unwrap();
+        assert_eq!(ram.alloc(5), None);
+        let third = ram.alloc(4).unwrap();
+        assert_eq!(ram.alloc(1), None);
+
+        assert_eq!((first.offset, first.len()), (0, 4));
+        assert_eq!((second.offset, second.len()), (4, TRAM_WORDS - 8));
+        assert_eq!((third.offset, third.len()), (TRAM_WORDS - 4, 4));
+    }
+
+    #[test]
+    fn test_read_write_clear() {
+        let mut words = [0u32; TRAM_WORDS];
+        {
+            let mut ram = SecretRam {
+                base: words.as_mut_ptr(),
+                next: 0,
+            };
+            let first = ram.alloc(2).unwrap();
+            let second = ram.alloc(3).unwrap();
+
+            assert_eq!(ram.write(&first, &[1, 2]), Ok(()));
+            assert_eq!(ram.write(&second, &[3, 4, 5]), Ok(()));
+            assert_eq!(ram.write(&second, &[3, 4]), Err(Error::InvalidLength));
+
+            let mut secret = [0u32; 3];
+            assert_eq!(ram.read(&second, &mut secret), Ok(()));
+            assert_eq!(secret, [3, 4, 5]);
+            assert_eq!(ram.read(&first, &mut secret), Err(Error::InvalidLength));
+
+            ram.clear(first.offset, first.len());
+        }
+        assert_eq!(words[..6], [0, 0, 3, 4, 5, 0]);
+    }
+
+    #[test]
+    fn test_aes128_key() {
+        let key = hex!("2b7e151628aed2a6abf7158809cf4f3c");
+        let mut key_words = [0u32; AES128_KEY_WORDS];
+        for (word, chunk) in key_words.iter_mut().zip(key.chunks(4)) {
+            *word = u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+
+        let mut words = [0u32; TRAM_WORDS];
+        let mut ram = SecretRam {
+            base: words.as_mut_ptr(),
+            next: 0,
+        };
+        let short = ram.alloc(3).unwrap();
+        let slot = ram.alloc(AES128_KEY_WORDS).unwrap();
+        ram.write(&slot, &key_words).unwrap();
+        assert!(ram.aes128_key(&short).is_err());
+
+        // FIPS-197 appendix B
+        let mut block = hex!("3243f6a8885a308d313198a2e0370734");
+        ram.aes128_key(&slot).unwrap().encrypt_block(&mut block);
+        assert_eq!(block, hex!("3925841d02dc09fbdc118597196a0b32"));
+    }
+}